@@ -0,0 +1,401 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bricks::{get_brick_type_count, Brick},
+    settings::{
+        despawn_settings_ui, highlight_settings_buttons, load_settings, save_settings,
+        spawn_settings_button,
+    },
+    theme::Theme,
+    AppState, GameConfig,
+};
+
+const ACCESSIBILITY_SETTINGS_FILE: &str = "accessibility.yaml";
+// Lightness of the brick borders relative to their fill
+const BORDER_LIGHTNESS: f32 = 0.5;
+const LABEL_FONT_RATIO: f32 = 0.45;
+const PIP_SIZE: f32 = 0.14;
+const PIP_SPACING: f32 = 0.25;
+// Patterns go up to a full 3x3 grid, more brick types are labeled with their score
+const MAX_PIPS: usize = 9;
+
+// Widest bricks first
+const OKABE_ITO: [[u8; 3]; 7] = [
+    [0xe6, 0x9f, 0x00], // Orange
+    [0x56, 0xb4, 0xe9], // Sky blue
+    [0x00, 0x9e, 0x73], // Bluish green
+    [0xf0, 0xe4, 0x42], // Yellow
+    [0x00, 0x72, 0xb2], // Blue
+    [0xd5, 0x5e, 0x00], // Vermillion
+    [0xcc, 0x79, 0xa7], // Reddish purple
+];
+const TOL_BRIGHT: [[u8; 3]; 7] = [
+    [0x44, 0x77, 0xaa], // Blue
+    [0xee, 0x66, 0x77], // Red
+    [0x22, 0x88, 0x33], // Green
+    [0xcc, 0xbb, 0x44], // Yellow
+    [0x66, 0xcc, 0xee], // Cyan
+    [0xaa, 0x33, 0x77], // Purple
+    [0xbb, 0xbb, 0xbb], // Grey
+];
+// Brightest for the widest bricks, the value is readable from the lightness alone
+const VIRIDIS: [[u8; 3]; 7] = [
+    [0xfd, 0xe7, 0x25],
+    [0x90, 0xd7, 0x43],
+    [0x35, 0xb7, 0x79],
+    [0x21, 0x91, 0x8c],
+    [0x31, 0x68, 0x8e],
+    [0x44, 0x39, 0x83],
+    [0x44, 0x01, 0x54],
+];
+
+/// Brick colors, `Theme` keeps the colors of the theme file.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BrickPalette {
+    #[default]
+    Theme,
+    OkabeIto,
+    TolBright,
+    Viridis,
+}
+
+impl BrickPalette {
+    const ALL: [BrickPalette; 4] = [
+        BrickPalette::Theme,
+        BrickPalette::OkabeIto,
+        BrickPalette::TolBright,
+        BrickPalette::Viridis,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            BrickPalette::Theme => "Theme",
+            BrickPalette::OkabeIto => "Okabe-Ito",
+            BrickPalette::TolBright => "Tol bright",
+            BrickPalette::Viridis => "Viridis",
+        }
+    }
+
+    fn colors(&self) -> Option<&'static [[u8; 3]]> {
+        match self {
+            BrickPalette::Theme => None,
+            BrickPalette::OkabeIto => Some(&OKABE_ITO),
+            BrickPalette::TolBright => Some(&TOL_BRIGHT),
+            BrickPalette::Viridis => Some(&VIRIDIS),
+        }
+    }
+
+    /// Replaces the brick colors of the theme, the borders are darker shades of the fill.
+    /// With a brick type count the palette is stretched so every type has its own color.
+    pub fn apply(&self, theme: &mut Theme, brick_type_count: Option<usize>) {
+        let Some(colors) = self.colors() else {
+            return;
+        };
+        theme.bricks = stretch_palette(colors, brick_type_count.unwrap_or(colors.len()));
+        theme.brick_borders = theme
+            .bricks
+            .iter()
+            .map(|color| {
+                let [h, s, l, a] = color.as_hsla_f32();
+                Color::hsla(h, s, l * BORDER_LIGHTNESS, a)
+            })
+            .collect();
+    }
+}
+
+/// Spreads `count` colors from the first to the last of the palette, blending neighbouring
+/// colors when there are more than the palette has.
+fn stretch_palette(colors: &[[u8; 3]], count: usize) -> Vec<Color> {
+    let last = colors.len() - 1;
+    (0..count)
+        .map(|index| {
+            let mut position = index as f32 * last as f32 / count.saturating_sub(1).max(1) as f32;
+            if count <= colors.len() {
+                position = position.round();
+            }
+            let low = (position.floor() as usize).min(last);
+            let high = (low + 1).min(last);
+            let blend = position - low as f32;
+            let [r, g, b] = [0, 1, 2].map(|channel| {
+                let low = colors[low][channel] as f32;
+                let high = colors[high][channel] as f32;
+                (low + (high - low) * blend) / 255.0
+            });
+            Color::rgb(r, g, b)
+        })
+        .collect()
+}
+
+/// Marks drawn on each brick so its value is readable without the colors.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BrickLabels {
+    #[default]
+    None,
+    // Points of the brick
+    Score,
+    // Dice like pips, more pips for more valuable bricks, scores above `MAX_PIPS` brick types
+    Pattern,
+}
+
+impl BrickLabels {
+    const ALL: [BrickLabels; 3] = [BrickLabels::None, BrickLabels::Score, BrickLabels::Pattern];
+
+    fn name(&self) -> &'static str {
+        match self {
+            BrickLabels::None => "None",
+            BrickLabels::Score => "Score",
+            BrickLabels::Pattern => "Pattern",
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    pub brick_palette: BrickPalette,
+    pub brick_labels: BrickLabels,
+}
+
+#[derive(Component, Debug)]
+struct BrickLabel;
+
+#[derive(Component, Debug)]
+struct AccessibilitySettingsUI;
+
+#[derive(Component, Debug)]
+enum AccessibilityButton {
+    BrickPalette,
+    BrickLabels,
+}
+
+pub struct AccessibilityPlugin;
+
+impl Plugin for AccessibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_settings::<AccessibilitySettings>(
+            ACCESSIBILITY_SETTINGS_FILE,
+        ))
+        .add_systems(Update, label_bricks)
+        .add_systems(
+            OnEnter(AppState::SelectConfig),
+            spawn_accessibility_settings_ui,
+        )
+        .add_systems(
+            OnExit(AppState::SelectConfig),
+            despawn_settings_ui::<AccessibilitySettingsUI>,
+        )
+        .add_systems(
+            Update,
+            (
+                highlight_settings_buttons::<AccessibilityButton>,
+                handle_accessibility_settings_click,
+                (
+                    despawn_settings_ui::<AccessibilitySettingsUI>,
+                    spawn_accessibility_settings_ui,
+                )
+                    .run_if(
+                        resource_changed::<AccessibilitySettings>()
+                            .or_else(resource_changed::<Theme>()),
+                    ),
+            )
+                .chain()
+                .run_if(in_state(AppState::SelectConfig)),
+        )
+        .add_systems(Update, save_accessibility_settings);
+    }
+}
+
+/// Bricks are only spawned outside of the config menu, where the settings can't change.
+fn label_bricks(
+    mut commands: Commands,
+    brick_query: Query<(Entity, &Brick, &Transform), Added<Brick>>,
+    accessibility_settings: Res<AccessibilitySettings>,
+    theme: Res<Theme>,
+    game_config: Option<Res<GameConfig>>,
+) {
+    let Some(game_config) = game_config else {
+        return;
+    };
+    let brick_type_count = get_brick_type_count(&game_config);
+    let brick_labels = match accessibility_settings.brick_labels {
+        BrickLabels::Pattern if brick_type_count > MAX_PIPS => BrickLabels::Score,
+        brick_labels => brick_labels,
+    };
+    for (brick_entity, brick, brick_transform) in brick_query.iter() {
+        let color = theme.brick_border_color(brick.brick_type);
+        match brick_labels {
+            BrickLabels::None => {}
+            BrickLabels::Score => {
+                // Counter the brick scale so the text is laid out in pixels
+                let scale = brick_transform.scale.x.max(1.0);
+                commands.entity(brick_entity).with_children(|parent| {
+                    parent.spawn((
+                        Text2dBundle {
+                            text: Text::from_section(
+                                brick.score.to_string(),
+                                TextStyle {
+                                    font_size: scale * LABEL_FONT_RATIO,
+                                    color,
+                                    ..default()
+                                },
+                            ),
+                            transform: Transform {
+                                translation: Vec3::new(0.0, 0.0, 2.0),
+                                scale: Vec3::new(1.0 / scale, 1.0 / scale, 1.0),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        BrickLabel,
+                    ));
+                });
+            }
+            BrickLabels::Pattern => {
+                let rank = brick_type_count.saturating_sub(brick.brick_type).max(1);
+                commands.entity(brick_entity).with_children(|parent| {
+                    for (x, y) in pip_positions(rank) {
+                        parent.spawn((
+                            SpriteBundle {
+                                sprite: Sprite { color, ..default() },
+                                transform: Transform {
+                                    translation: Vec3::new(
+                                        x as f32 * PIP_SPACING,
+                                        y as f32 * PIP_SPACING,
+                                        2.0,
+                                    ),
+                                    scale: Vec3::new(PIP_SIZE, PIP_SIZE, 1.0),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            BrickLabel,
+                        ));
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Cells of a 3x3 grid, from -1 to 1, laid out like the faces of a die.
+fn pip_positions(count: usize) -> Vec<(i32, i32)> {
+    let mut positions = match count {
+        1 => vec![],
+        2 | 3 => vec![(-1, 1), (1, -1)],
+        4 | 5 => vec![(-1, 1), (1, 1), (-1, -1), (1, -1)],
+        _ => vec![(-1, 1), (1, 1), (-1, 0), (1, 0), (-1, -1), (1, -1)],
+    };
+    if count == 8 || count == 9 {
+        positions.extend([(0, 1), (0, -1)]);
+    }
+    if count % 2 == 1 {
+        positions.push((0, 0));
+    }
+    positions
+}
+
+fn save_accessibility_settings(accessibility_settings: Res<AccessibilitySettings>) {
+    if !accessibility_settings.is_changed() || accessibility_settings.is_added() {
+        return;
+    }
+    if let Err(e) = save_settings(ACCESSIBILITY_SETTINGS_FILE, &*accessibility_settings) {
+        error!("{e}");
+    }
+}
+
+fn spawn_accessibility_settings_ui(
+    mut commands: Commands,
+    accessibility_settings: Res<AccessibilitySettings>,
+    theme: Res<Theme>,
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::End,
+                    row_gap: Val::Px(6.0),
+                    right: Val::Px(10.0),
+                    // Below the theme button
+                    top: Val::Px(44.0),
+                    ..default()
+                },
+                ..default()
+            },
+            AccessibilitySettingsUI,
+        ))
+        .with_children(|parent| {
+            spawn_settings_button(
+                parent,
+                &theme,
+                &format!(
+                    "Brick colors: {}",
+                    accessibility_settings.brick_palette.name()
+                ),
+                AccessibilityButton::BrickPalette,
+            );
+            spawn_settings_button(
+                parent,
+                &theme,
+                &format!(
+                    "Brick labels: {}",
+                    accessibility_settings.brick_labels.name()
+                ),
+                AccessibilityButton::BrickLabels,
+            );
+        });
+}
+
+fn handle_accessibility_settings_click(
+    button_query: Query<(&AccessibilityButton, &Interaction), Changed<Interaction>>,
+    mut accessibility_settings: ResMut<AccessibilitySettings>,
+) {
+    for (button, interaction) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            AccessibilityButton::BrickPalette => {
+                let palettes = BrickPalette::ALL;
+                let index = palettes
+                    .iter()
+                    .position(|palette| *palette == accessibility_settings.brick_palette)
+                    .unwrap_or_default();
+                accessibility_settings.brick_palette = palettes[(index + 1) % palettes.len()];
+            }
+            AccessibilityButton::BrickLabels => {
+                let labels = BrickLabels::ALL;
+                let index = labels
+                    .iter()
+                    .position(|labels| *labels == accessibility_settings.brick_labels)
+                    .unwrap_or_default();
+                accessibility_settings.brick_labels = labels[(index + 1) % labels.len()];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_stretches_over_every_brick_type() {
+        let few = stretch_palette(&VIRIDIS, 3);
+        assert_eq!(few[0], Color::rgb_u8(0xfd, 0xe7, 0x25));
+        assert_eq!(few[1], Color::rgb_u8(0x21, 0x91, 0x8c));
+        assert_eq!(few[2], Color::rgb_u8(0x44, 0x01, 0x54));
+
+        let many = stretch_palette(&OKABE_ITO, 11);
+        assert_eq!(many.len(), 11);
+        assert_eq!(many[0], Color::rgb_u8(0xe6, 0x9f, 0x00));
+        assert_eq!(many[10], Color::rgb_u8(0xcc, 0x79, 0xa7));
+        for (index, color) in many.iter().enumerate() {
+            assert!(!many[index + 1..].contains(color));
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{BrickDestroyed, FieldCleared, GameplaySet, PaddleHit, WallHit},
+    level::EditorLevel,
+    profile::{ActiveProfile, Profile},
+    score::Score,
+    theme::Theme,
+    walls::WallLocation,
+    AppState, GameConfig,
+};
+
+const ONE_BOUNCE_BRICKS: i32 = 10;
+const SKYSCRAPER_CONFIG: &str = "skyscraper.yaml";
+const TOAST_SECONDS: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Achievement {
+    NoSideWalls,
+    OneBounceTen,
+    SkyscraperWin,
+    PositiveScore,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 4] = [
+        Achievement::NoSideWalls,
+        Achievement::OneBounceTen,
+        Achievement::SkyscraperWin,
+        Achievement::PositiveScore,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Achievement::NoSideWalls => "Straight shooter",
+            Achievement::OneBounceTen => "Chain reaction",
+            Achievement::SkyscraperWin => "Demolition crew",
+            Achievement::PositiveScore => "In the black",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Achievement::NoSideWalls => "Clear a field without the ball touching the side walls",
+            Achievement::OneBounceTen => "Destroy 10 bricks in one paddle bounce",
+            Achievement::SkyscraperWin => "Win on skyscraper.yaml",
+            Achievement::PositiveScore => "Finish a game with a positive score",
+        }
+    }
+}
+
+/// Progress of the current round towards the round based achievements, saved with the round.
+#[derive(Resource, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct AchievementTracker {
+    side_wall_hits: i32,
+    bounce_bricks: i32,
+}
+
+#[derive(Component, Debug)]
+struct AchievementToast(Timer);
+
+pub struct AchievementsPlugin;
+
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AchievementTracker::default())
+            .add_systems(OnEnter(AppState::InGame), reset_achievement_tracker)
+            // Editor play-tests unlock nothing
+            .add_systems(
+                Update,
+                track_achievements.in_set(GameplaySet::Consequences).run_if(
+                    in_state(AppState::InGame).and_then(not(resource_exists::<EditorLevel>())),
+                ),
+            )
+            .add_systems(
+                OnEnter(AppState::GaveOver),
+                check_final_score.run_if(not(resource_exists::<EditorLevel>())),
+            )
+            .add_systems(Update, expire_achievement_toasts);
+    }
+}
+
+fn reset_achievement_tracker(mut achievement_tracker: ResMut<AchievementTracker>) {
+    *achievement_tracker = AchievementTracker::default();
+}
+
+fn unlock(
+    commands: &mut Commands,
+    active_profile: &mut ActiveProfile,
+    theme: &Theme,
+    achievement: Achievement,
+) {
+    if !active_profile.0.achievements.insert(achievement) {
+        return;
+    }
+    info!("achievement unlocked: {}", achievement.title());
+    if let Err(e) = active_profile.0.save() {
+        error!("{e}");
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    justify_content: JustifyContent::Center,
+                    left: Val::Percent(35.0),
+                    top: Val::Px(10.0),
+                    width: Val::Percent(30.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                background_color: theme.panel.into(),
+                z_index: ZIndex::Global(2),
+                ..default()
+            },
+            AchievementToast(Timer::from_seconds(TOAST_SECONDS, TimerMode::Once)),
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_sections([
+                    TextSection::new(
+                        format!("Achievement unlocked: {}\n", achievement.title()),
+                        TextStyle {
+                            font_size: 24.0,
+                            color: theme.text_highlight,
+                            ..default()
+                        },
+                    ),
+                    TextSection::new(
+                        achievement.description(),
+                        TextStyle {
+                            font_size: 18.0,
+                            color: theme.text,
+                            ..default()
+                        },
+                    ),
+                ])
+                .with_text_alignment(TextAlignment::Center),
+            );
+        });
+}
+
+fn track_achievements(
+    mut commands: Commands,
+    mut brick_destroyed_events: EventReader<BrickDestroyed>,
+    mut paddle_hit_events: EventReader<PaddleHit>,
+    mut wall_hit_events: EventReader<WallHit>,
+    mut field_cleared_events: EventReader<FieldCleared>,
+    mut achievement_tracker: ResMut<AchievementTracker>,
+    mut active_profile: ResMut<ActiveProfile>,
+    theme: Res<Theme>,
+    game_config: Res<GameConfig>,
+) {
+    for wall_hit in wall_hit_events.read() {
+        if let WallLocation::Left | WallLocation::Right = wall_hit.location {
+            achievement_tracker.side_wall_hits += 1;
+        }
+    }
+    // Bricks and paddle hits of the same frame are not ordered, count the paddle hits first
+    if paddle_hit_events.read().count() > 0 {
+        achievement_tracker.bounce_bricks = 0;
+    }
+    for _ in brick_destroyed_events.read() {
+        achievement_tracker.bounce_bricks += 1;
+        if achievement_tracker.bounce_bricks == ONE_BOUNCE_BRICKS {
+            unlock(
+                &mut commands,
+                &mut active_profile,
+                &theme,
+                Achievement::OneBounceTen,
+            );
+        }
+    }
+
+    if field_cleared_events.read().count() == 0 {
+        return;
+    }
+    if achievement_tracker.side_wall_hits == 0 {
+        unlock(
+            &mut commands,
+            &mut active_profile,
+            &theme,
+            Achievement::NoSideWalls,
+        );
+    }
+    if game_config.file_name == SKYSCRAPER_CONFIG {
+        unlock(
+            &mut commands,
+            &mut active_profile,
+            &theme,
+            Achievement::SkyscraperWin,
+        );
+    }
+}
+
+fn check_final_score(
+    mut commands: Commands,
+    score: Res<Score>,
+    mut active_profile: ResMut<ActiveProfile>,
+    theme: Res<Theme>,
+) {
+    if score.score > 0 {
+        unlock(
+            &mut commands,
+            &mut active_profile,
+            &theme,
+            Achievement::PositiveScore,
+        );
+    }
+}
+
+fn expire_achievement_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toast_query: Query<(Entity, &mut AchievementToast)>,
+) {
+    for (entity, mut toast) in toast_query.iter_mut() {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Unlocked and locked achievements of a player, for the config menu.
+pub fn spawn_achievement_list(parent: &mut ChildBuilder, theme: &Theme, profile: &Profile) {
+    let sections = Achievement::ALL.iter().map(|achievement| {
+        let unlocked = profile.achievements.contains(achievement);
+        TextSection::new(
+            format!(
+                "\n[{}] {} - {}",
+                if unlocked { "x" } else { " " },
+                achievement.title(),
+                achievement.description()
+            ),
+            TextStyle {
+                font_size: 16.0,
+                color: if unlocked {
+                    theme.text_highlight
+                } else {
+                    theme.text.with_a(0.5)
+                },
+                ..default()
+            },
+        )
+    });
+    parent.spawn(TextBundle::from_sections(
+        [TextSection::new(
+            format!(
+                "Achievements: {}/{}",
+                profile.achievements.len(),
+                Achievement::ALL.len()
+            ),
+            TextStyle {
+                font_size: 18.0,
+                color: theme.text,
+                ..default()
+            },
+        )]
+        .into_iter()
+        .chain(sections),
+    ));
+}
@@ -0,0 +1,356 @@
+use bevy::{
+    audio::{PlaybackMode, Volume},
+    prelude::*,
+};
+use cpal::traits::HostTrait;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::{
+    bricks::get_brick_color_index,
+    events::{BallLost, BrickDestroyed, FieldCleared, GameplaySet, PaddleHit, WallHit},
+    settings::{
+        despawn_settings_ui, highlight_settings_buttons, load_settings, save_settings,
+        spawn_settings_button,
+    },
+    theme::Theme,
+    AppState, GameConfig,
+};
+
+const AUDIO_SETTINGS_FILE: &str = "audio.yaml";
+const MUTE_KEY: KeyCode = KeyCode::M;
+const VOLUME_STEP: f32 = 0.1;
+// Generated tones are full scale sine waves, far louder than recorded sounds
+const TONE_VOLUME: f32 = 0.25;
+// Pentatonic notes, one per brick color band, smaller bricks sound higher
+const BRICK_NOTES: [f32; 7] = [261.63, 293.66, 329.63, 392.0, 440.0, 523.25, 587.33];
+const PADDLE_NOTE: f32 = 196.0;
+const WALL_NOTE: f32 = 130.81;
+const DEATH_NOTE: f32 = 82.41;
+const WIN_NOTE: f32 = 783.99;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AudioChannel {
+    Master,
+    Sfx,
+    Music,
+}
+
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub sfx: f32,
+    pub music: f32,
+    pub muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            master: 0.8,
+            sfx: 1.0,
+            music: 0.6,
+            muted: false,
+        }
+    }
+}
+
+impl AudioSettings {
+    fn channel(&mut self, channel: AudioChannel) -> &mut f32 {
+        match channel {
+            AudioChannel::Master => &mut self.master,
+            AudioChannel::Sfx => &mut self.sfx,
+            AudioChannel::Music => &mut self.music,
+        }
+    }
+
+    fn volume(&self, channel: AudioChannel) -> f32 {
+        if self.muted {
+            return 0.0;
+        }
+        match channel {
+            AudioChannel::Master => self.master,
+            AudioChannel::Sfx => self.master * self.sfx,
+            AudioChannel::Music => self.master * self.music,
+        }
+    }
+}
+
+/// Where sounds go, the null backend drops them when there is no output device.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+enum AudioBackend {
+    Output,
+    Null,
+}
+
+impl AudioBackend {
+    fn detect() -> Self {
+        if cpal::default_host().default_output_device().is_some() {
+            AudioBackend::Output
+        } else {
+            warn!("no audio output device, sounds are disabled");
+            AudioBackend::Null
+        }
+    }
+}
+
+#[derive(Resource, Debug)]
+struct SoundEffects {
+    paddle: Handle<Pitch>,
+    wall: Handle<Pitch>,
+    death: Handle<Pitch>,
+    win: Handle<Pitch>,
+    bricks: Vec<Handle<Pitch>>,
+}
+
+#[derive(Component, Debug)]
+struct Music;
+
+#[derive(Component, Debug)]
+struct AudioSettingsUI;
+
+#[derive(Component, Debug)]
+enum AudioButton {
+    Decrease(AudioChannel),
+    Increase(AudioChannel),
+    Mute,
+}
+
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_settings::<AudioSettings>(AUDIO_SETTINGS_FILE))
+            .insert_resource(AudioBackend::detect())
+            .add_systems(Startup, load_sound_effects)
+            .add_systems(
+                Update,
+                play_sound_effects
+                    .in_set(GameplaySet::Consequences)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(OnEnter(AppState::InGame), play_music)
+            .add_systems(OnEnter(AppState::SelectConfig), stop_music)
+            .add_systems(OnEnter(AppState::Editor), stop_music)
+            .add_systems(OnEnter(AppState::SelectConfig), spawn_audio_settings_ui)
+            .add_systems(
+                OnExit(AppState::SelectConfig),
+                despawn_settings_ui::<AudioSettingsUI>,
+            )
+            .add_systems(
+                Update,
+                (
+                    highlight_settings_buttons::<AudioButton>,
+                    handle_audio_click,
+                    (
+                        despawn_settings_ui::<AudioSettingsUI>,
+                        spawn_audio_settings_ui,
+                    )
+                        .run_if(
+                            resource_changed::<AudioSettings>()
+                                .or_else(resource_changed::<Theme>()),
+                        ),
+                )
+                    .chain()
+                    .run_if(in_state(AppState::SelectConfig)),
+            )
+            .add_systems(
+                Update,
+                toggle_mute
+                    .run_if(in_state(AppState::InGame).or_else(in_state(AppState::GaveOver))),
+            )
+            .add_systems(Update, apply_audio_settings);
+    }
+}
+
+fn load_sound_effects(mut commands: Commands, mut pitches: ResMut<Assets<Pitch>>) {
+    let mut tone = |frequency: f32, millis: u64| {
+        pitches.add(Pitch::new(frequency, Duration::from_millis(millis)))
+    };
+    commands.insert_resource(SoundEffects {
+        paddle: tone(PADDLE_NOTE, 60),
+        wall: tone(WALL_NOTE, 40),
+        death: tone(DEATH_NOTE, 600),
+        win: tone(WIN_NOTE, 800),
+        bricks: BRICK_NOTES
+            .iter()
+            .map(|frequency| tone(*frequency, 80))
+            .collect(),
+    });
+}
+
+fn play_sound_effects(
+    mut commands: Commands,
+    mut brick_destroyed_events: EventReader<BrickDestroyed>,
+    mut paddle_hit_events: EventReader<PaddleHit>,
+    mut wall_hit_events: EventReader<WallHit>,
+    mut ball_lost_events: EventReader<BallLost>,
+    mut field_cleared_events: EventReader<FieldCleared>,
+    sound_effects: Res<SoundEffects>,
+    audio_settings: Res<AudioSettings>,
+    audio_backend: Res<AudioBackend>,
+    game_config: Res<GameConfig>,
+) {
+    let mut sounds: Vec<&Handle<Pitch>> = Vec::new();
+    sounds.extend(paddle_hit_events.read().map(|_| &sound_effects.paddle));
+    sounds.extend(wall_hit_events.read().map(|_| &sound_effects.wall));
+    sounds.extend(brick_destroyed_events.read().map(|brick_destroyed| {
+        let index = get_brick_color_index(brick_destroyed.size, &game_config);
+        &sound_effects.bricks[index % sound_effects.bricks.len()]
+    }));
+    sounds.extend(ball_lost_events.read().map(|_| &sound_effects.death));
+    sounds.extend(field_cleared_events.read().map(|_| &sound_effects.win));
+
+    let volume = audio_settings.volume(AudioChannel::Sfx) * TONE_VOLUME;
+    if *audio_backend == AudioBackend::Null || volume <= 0.0 {
+        return;
+    }
+    for sound in sounds {
+        commands.spawn(PitchBundle {
+            source: sound.clone(),
+            settings: PlaybackSettings::DESPAWN.with_volume(Volume::new_relative(volume)),
+        });
+    }
+}
+
+fn play_music(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    music_query: Query<(), With<Music>>,
+    audio_settings: Res<AudioSettings>,
+    audio_backend: Res<AudioBackend>,
+    game_config: Res<GameConfig>,
+) {
+    let Some(music) = &game_config.music else {
+        return;
+    };
+    if *audio_backend == AudioBackend::Null || !music_query.is_empty() {
+        return;
+    }
+
+    commands.spawn((
+        AudioBundle {
+            source: asset_server.load(music.clone()),
+            settings: PlaybackSettings {
+                mode: PlaybackMode::Loop,
+                volume: Volume::new_relative(audio_settings.volume(AudioChannel::Music)),
+                ..default()
+            },
+        },
+        Music,
+    ));
+}
+
+fn stop_music(mut commands: Commands, music_query: Query<Entity, With<Music>>) {
+    for entity in music_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn toggle_mute(mut audio_settings: ResMut<AudioSettings>, keyboard_input: Res<Input<KeyCode>>) {
+    if keyboard_input.just_pressed(MUTE_KEY) {
+        audio_settings.muted = !audio_settings.muted;
+    }
+}
+
+fn apply_audio_settings(
+    audio_settings: Res<AudioSettings>,
+    music_query: Query<&AudioSink, With<Music>>,
+) {
+    if !audio_settings.is_changed() || audio_settings.is_added() {
+        return;
+    }
+
+    for sink in music_query.iter() {
+        sink.set_volume(audio_settings.volume(AudioChannel::Music));
+    }
+    if let Err(e) = save_settings(AUDIO_SETTINGS_FILE, &*audio_settings) {
+        error!("{e}");
+    }
+}
+
+fn spawn_audio_settings_ui(
+    mut commands: Commands,
+    audio_settings: Res<AudioSettings>,
+    theme: Res<Theme>,
+) {
+    let text_style = TextStyle {
+        font_size: 20.0,
+        color: theme.text,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    display: Display::Grid,
+                    grid_template_columns: RepeatedGridTrack::auto(4),
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(6.0),
+                    row_gap: Val::Px(6.0),
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            },
+            AudioSettingsUI,
+        ))
+        .with_children(|parent| {
+            for (label, channel) in [
+                ("Master", AudioChannel::Master),
+                ("Effects", AudioChannel::Sfx),
+                ("Music", AudioChannel::Music),
+            ] {
+                let volume = match channel {
+                    AudioChannel::Master => audio_settings.master,
+                    AudioChannel::Sfx => audio_settings.sfx,
+                    AudioChannel::Music => audio_settings.music,
+                };
+                parent.spawn(TextBundle::from_section(label, text_style.clone()));
+                spawn_settings_button(parent, &theme, "-", AudioButton::Decrease(channel));
+                parent.spawn(TextBundle::from_section(
+                    format!("{:>3.0}%", volume * 100.0),
+                    text_style.clone(),
+                ));
+                spawn_settings_button(parent, &theme, "+", AudioButton::Increase(channel));
+            }
+            spawn_settings_button(
+                parent,
+                &theme,
+                if audio_settings.muted {
+                    "Unmute [M]"
+                } else {
+                    "Mute [M]"
+                },
+                AudioButton::Mute,
+            );
+        });
+}
+
+fn handle_audio_click(
+    button_query: Query<(&AudioButton, &Interaction), Changed<Interaction>>,
+    mut audio_settings: ResMut<AudioSettings>,
+) {
+    for (button, interaction) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            AudioButton::Decrease(channel) => {
+                let volume = audio_settings.channel(*channel);
+                *volume = (*volume - VOLUME_STEP).max(0.0);
+            }
+            AudioButton::Increase(channel) => {
+                let volume = audio_settings.channel(*channel);
+                *volume = (*volume + VOLUME_STEP).min(1.0);
+            }
+            AudioButton::Mute => {
+                audio_settings.muted = !audio_settings.muted;
+            }
+        }
+    }
+}
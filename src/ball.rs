@@ -1,13 +1,24 @@
 use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
 use bevy_rapier2d::prelude::*;
+use rand::prelude::*;
 
-use crate::{bricks::Brick, paddle::Paddle, score::Score, walls::Death, AppState, GameConfig};
+use crate::{
+    bricks::Brick,
+    effects::Squash,
+    events::{
+        BallLost, BrickDestroyed, FieldCleared, GameplayEventWriters, GameplaySet, PaddleHit,
+        WallHit,
+    },
+    layout_debug::LayoutDebugger,
+    paddle::{HorizontalVelocity, Paddle},
+    profile::ActiveProfile,
+    theme::{Skins, Theme},
+    walls::{Boost, Bouncy, Death, Portal, WallLocation},
+    AppState, GameConfig, DEGREE_TO_RADIAN_FACTOR,
+};
 
 // const BALL_COLOR: Color = Color::rgb(1.0, 0.5, 0.5);
 // const BALL_BORDER_COLOR: Color = Color::rgb(0.05, 0.05, 0.05);
-const BALL_COLOR: Color = Color::hsl(0.0, 1.0, 0.75);
-const BALL_BORDER_COLOR: Color = Color::hsl(0.0, 1.0, 0.1);
-const BALL_BORDER_WIDTH: f32 = 2.0;
 // const BALL_STARTING_POSITION: Vec3 = Vec3::new(-200.0, -170.0, 1.0);
 // const BALL_RADIUS: f32 = 30.0;
 // const BALL_INITIAL_SPEED: f32 = 50.0;
@@ -21,22 +32,73 @@ pub struct Ball;
 
 #[derive(Component, Debug)]
 pub struct BallTopSpeed {
-    speed: f32,
+    pub speed: f32,
+    pub score_gained: i32,
+    pub bricks_destroyed: i32,
 }
 #[derive(Resource, Debug)]
-struct BallAirTime(f32);
+pub struct BallAirTime(pub f32);
+
+/// Ball riding the paddle until served, `timeout` launches it automatically.
+#[derive(Component, Debug)]
+pub struct Serving {
+    pub timeout: Option<Timer>,
+}
+
+/// Shallow trajectory and bounce loop tracking, `nudges` counts corrections this game.
+/// `bounces` holds what the ball bounced off and where it left to since the last brick.
+#[derive(Resource, Debug, Default)]
+pub struct BallTrajectory {
+    shallow_time: f32,
+    bounces: Vec<(Entity, Vec2)>,
+    loop_detected: bool,
+    pub nudges: i32,
+}
+
+// Largest angle between two outgoing directions counted as the same bounce, in degrees
+const BOUNCE_LOOP_TOLERANCE: f32 = 0.5;
 
 pub struct BallPlugin;
 
 impl Plugin for BallPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(BallAirTime(0.0))
+            .insert_resource(BallTrajectory::default())
             .add_systems(OnEnter(AppState::InGame), spawn_ball)
             .add_systems(OnExit(AppState::InGame), despawn_ball)
-            .add_systems(Update, ball_collision.run_if(in_state(AppState::InGame)))
             .add_systems(
                 Update,
-                update_ball_air_time.run_if(in_state(AppState::InGame)),
+                ball_collision
+                    .in_set(GameplaySet::Collisions)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                Update,
+                speed_up_ball
+                    .in_set(GameplaySet::Consequences)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                Update,
+                (
+                    update_ball_air_time,
+                    serve_ball,
+                    wrap_ball,
+                    decay_ball_speed,
+                )
+                    .run_if(
+                        in_state(AppState::InGame)
+                            .and_then(not(resource_exists::<LayoutDebugger>())),
+                    ),
+            )
+            .add_systems(
+                Update,
+                correct_ball_trajectory
+                    .after(GameplaySet::Collisions)
+                    .run_if(
+                        in_state(AppState::InGame)
+                            .and_then(not(resource_exists::<LayoutDebugger>())),
+                    ),
             );
     }
 }
@@ -45,55 +107,97 @@ fn spawn_ball(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_trajectory: ResMut<BallTrajectory>,
+    theme: Res<Theme>,
+    skins: Res<Skins>,
     game_config: Res<GameConfig>,
 ) {
-    commands
-        .spawn((
-            Ball,
-            BallTopSpeed {
-                speed: game_config.ball_speed,
-            },
-            MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Circle::default().into()).into(),
-                material: materials.add(ColorMaterial::from(BALL_BORDER_COLOR)),
-                transform: Transform::from_translation(game_config.get_ball_starting_position())
-                    .with_scale(Vec3 {
-                        x: game_config.ball_diameter,
-                        y: game_config.ball_diameter,
-                        z: 2.0,
-                    }),
-                ..default()
-            },
-            RigidBody::Dynamic,
-            Velocity {
-                linvel: game_config.get_ball_initial_linvel(),
-                ..default()
+    *ball_trajectory = BallTrajectory::default();
+
+    let mut ball_commands = commands.spawn((
+        Ball,
+        BallTopSpeed {
+            speed: game_config.ball_speed,
+            score_gained: 0,
+            bricks_destroyed: 0,
+        },
+        SpatialBundle::from_transform(
+            Transform::from_translation(game_config.get_ball_starting_position()).with_scale(
+                Vec3 {
+                    x: game_config.ball_diameter,
+                    y: game_config.ball_diameter,
+                    z: 2.0,
+                },
+            ),
+        ),
+        RigidBody::Dynamic,
+        Velocity {
+            linvel: if game_config.ball_serve_on_paddle {
+                Vec2::ZERO
+            } else {
+                game_config.get_ball_initial_linvel()
             },
-            Collider::ball(0.5),
-            Friction::coefficient(0.0),
-            Restitution::coefficient(1.0),
-            GravityScale(0.0),
-            Ccd::enabled(),
-            ActiveEvents::COLLISION_EVENTS,
-        ))
-        .with_children(|parent| {
-            parent.spawn(MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Circle::default().into()).into(),
-                material: materials.add(ColorMaterial::from(BALL_COLOR)),
-                transform: Transform {
-                    translation: Vec3::new(0.0, 0.0, 3.0),
-                    scale: Vec3 {
-                        x: (game_config.ball_diameter - BALL_BORDER_WIDTH * 2.0)
-                            / game_config.ball_diameter,
-                        y: (game_config.ball_diameter - BALL_BORDER_WIDTH * 2.0)
-                            / game_config.ball_diameter,
-                        z: 1.0,
+            ..default()
+        },
+        Collider::ball(0.5),
+        Friction::coefficient(0.0),
+        Restitution::coefficient(1.0),
+        GravityScale(0.0),
+        Ccd::enabled(),
+        ActiveEvents::COLLISION_EVENTS,
+    ));
+    // The meshes are on a child so squashing them leaves the collider alone
+    ball_commands.with_children(|parent| {
+        if let Some(ball_image) = &skins.ball {
+            parent.spawn((
+                Squash::default(),
+                SpriteBundle {
+                    texture: ball_image.clone(),
+                    // Sized by the scale of the ball
+                    sprite: Sprite {
+                        custom_size: Some(Vec2::ONE),
+                        ..default()
                     },
                     ..default()
                 },
-                ..default()
+            ));
+            return;
+        }
+        parent
+            .spawn((
+                Squash::default(),
+                MaterialMesh2dBundle {
+                    mesh: meshes.add(shape::Circle::default().into()).into(),
+                    material: materials.add(ColorMaterial::from(theme.ball_border)),
+                    ..default()
+                },
+            ))
+            .with_children(|visual_parent| {
+                visual_parent.spawn(MaterialMesh2dBundle {
+                    mesh: meshes.add(shape::Circle::default().into()).into(),
+                    material: materials.add(ColorMaterial::from(theme.ball)),
+                    transform: Transform {
+                        translation: Vec3::new(0.0, 0.0, 3.0),
+                        scale: Vec3 {
+                            x: (game_config.ball_diameter - theme.ball_border_width * 2.0)
+                                / game_config.ball_diameter,
+                            y: (game_config.ball_diameter - theme.ball_border_width * 2.0)
+                                / game_config.ball_diameter,
+                            z: 1.0,
+                        },
+                        ..default()
+                    },
+                    ..default()
+                });
             });
+    });
+
+    if game_config.ball_serve_on_paddle {
+        ball_commands.insert(Serving {
+            timeout: (game_config.ball_serve_timeout > 0.0)
+                .then(|| Timer::from_seconds(game_config.ball_serve_timeout, TimerMode::Once)),
         });
+    }
 }
 
 fn despawn_ball(
@@ -106,6 +210,54 @@ fn despawn_ball(
     ball_air_time.0 = 0.0;
 }
 
+fn serve_ball(
+    mut commands: Commands,
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut ball_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut Serving,
+            &BallTopSpeed,
+        ),
+        With<Ball>,
+    >,
+    paddle_query: Query<(&Transform, &HorizontalVelocity), (With<Paddle>, Without<Ball>)>,
+    mut ball_air_time: ResMut<BallAirTime>,
+    active_profile: Res<ActiveProfile>,
+    game_config: Res<GameConfig>,
+) {
+    let Ok((ball_entity, mut ball_transform, mut ball_velocity, mut serving, ball_top_speed)) =
+        ball_query.get_single_mut()
+    else {
+        return;
+    };
+    let Ok((paddle_transform, paddle_velocity)) = paddle_query.get_single() else {
+        return;
+    };
+
+    // Keep the ball just above the top of the paddle arc, below it with the paddle at the top
+    ball_transform.translation.x = paddle_transform.translation.x;
+    ball_transform.translation.y = paddle_transform.translation.y
+        + (game_config.paddle_height + game_config.ball_diameter / 2.0 + 1.0)
+            * game_config.get_paddle_side_sign();
+    ball_velocity.linvel = Vec2::ZERO;
+    ball_air_time.0 = 0.0;
+
+    let timed_out = match &mut serving.timeout {
+        Some(timer) => timer.tick(time.delta()).just_finished(),
+        None => false,
+    };
+    if keyboard_input.any_just_pressed(active_profile.0.bindings.serve.iter().copied()) || timed_out
+    {
+        ball_velocity.linvel =
+            game_config.get_ball_serve_direction(paddle_velocity.value) * ball_top_speed.speed;
+        commands.entity(ball_entity).remove::<Serving>();
+    }
+}
+
 fn update_ball_air_time(
     time: Res<Time>,
     mut ball_air_time: ResMut<BallAirTime>,
@@ -116,29 +268,175 @@ fn update_ball_air_time(
     let (mut ball_gravity, mut ball_restitution) = ball_gravity_query.single_mut();
     (ball_gravity.0, ball_restitution.coefficient) =
         if ball_air_time.0 > game_config.ball_anti_gravity_time {
-            (game_config.ball_gravity_scale, game_config.ball_restitution)
+            // Pulled towards the paddle
+            (
+                game_config.ball_gravity_scale * game_config.get_paddle_side_sign(),
+                game_config.ball_restitution,
+            )
         } else {
             (0.0, 1.0)
         };
 }
 
+fn correct_ball_trajectory(
+    time: Res<Time>,
+    mut ball_query: Query<(Entity, &mut Velocity), With<Ball>>,
+    brick_query: Query<(), With<Brick>>,
+    paddle_query: Query<(), With<Paddle>>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut ball_trajectory: ResMut<BallTrajectory>,
+    game_config: Res<GameConfig>,
+) {
+    let Ok((ball_entity, mut ball_velocity)) = ball_query.get_single_mut() else {
+        return;
+    };
+
+    let mut paddle_hit = false;
+    for collision_event in collision_events.read() {
+        if let CollisionEvent::Stopped(entity1, entity2, _) = collision_event {
+            let other_entity = if ball_entity == *entity1 {
+                entity2
+            } else if ball_entity == *entity2 {
+                entity1
+            } else {
+                continue;
+            };
+            if brick_query.contains(*other_entity) {
+                ball_trajectory.bounces.clear();
+                ball_trajectory.loop_detected = false;
+                continue;
+            }
+            paddle_hit |= paddle_query.contains(*other_entity);
+            if game_config.ball_stuck_bounces > 0 {
+                let direction = ball_velocity.linvel.normalize_or_zero();
+                ball_trajectory.bounces.push((*other_entity, direction));
+                let excess = ball_trajectory
+                    .bounces
+                    .len()
+                    .saturating_sub(game_config.ball_stuck_bounces as usize);
+                ball_trajectory.bounces.drain(..excess);
+                if is_bounce_loop(&ball_trajectory.bounces, game_config.ball_stuck_bounces) {
+                    ball_trajectory.loop_detected = true;
+                }
+            }
+        }
+    }
+
+    let speed = ball_velocity.linvel.length();
+    if speed == 0.0 {
+        return;
+    }
+
+    let min_vertical_ratio =
+        (game_config.ball_min_trajectory_angle * DEGREE_TO_RADIAN_FACTOR).sin();
+    if ball_velocity.linvel.y.abs() / speed < min_vertical_ratio {
+        ball_trajectory.shallow_time += time.delta_seconds();
+    } else {
+        ball_trajectory.shallow_time = 0.0;
+    }
+
+    let nudge_angle = game_config.ball_nudge_angle * DEGREE_TO_RADIAN_FACTOR;
+    if ball_trajectory.shallow_time > game_config.ball_stuck_time {
+        // Steepen the trajectory, keeping its horizontal direction and sending it down when flat
+        let x_sign = if ball_velocity.linvel.x < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let y_sign = if ball_velocity.linvel.y > 0.0 {
+            1.0
+        } else {
+            -1.0
+        };
+        ball_velocity.linvel =
+            Vec2::new(x_sign * nudge_angle.cos(), y_sign * nudge_angle.sin()) * speed;
+        ball_trajectory.shallow_time = 0.0;
+        ball_trajectory.nudges += 1;
+    } else if ball_trajectory.loop_detected && !paddle_hit {
+        // Left alone on paddle hits so the aim of the player is kept until the next frame
+        let angle = if thread_rng().gen_bool(0.5) {
+            nudge_angle
+        } else {
+            -nudge_angle
+        };
+        let turned = Vec2::from_angle(angle).rotate(ball_velocity.linvel);
+        ball_velocity.linvel = clamp_trajectory_angle(
+            turned,
+            ball_velocity.linvel.y,
+            game_config.ball_min_trajectory_angle,
+        );
+        ball_trajectory.bounces.clear();
+        ball_trajectory.loop_detected = false;
+        ball_trajectory.nudges += 1;
+    }
+}
+
+// Whether the last `window` bounces repeat with a period of at most half of it, hitting the
+// same things and leaving in the same directions
+fn is_bounce_loop(bounces: &[(Entity, Vec2)], window: i32) -> bool {
+    let window = window as usize;
+    if window < 2 || bounces.len() < window {
+        return false;
+    }
+    let min_dot = (BOUNCE_LOOP_TOLERANCE * DEGREE_TO_RADIAN_FACTOR).cos();
+    (1..=window / 2).any(|period| {
+        (period..bounces.len()).all(|i| {
+            let (entity, direction) = bounces[i];
+            let (previous_entity, previous_direction) = bounces[i - period];
+            entity == previous_entity && direction.dot(previous_direction) >= min_dot
+        })
+    })
+}
+
+// Keeps `linvel` at least `min_angle` degrees off the horizontal, going up or down as `y` did
+fn clamp_trajectory_angle(linvel: Vec2, y: f32, min_angle: f32) -> Vec2 {
+    let speed = linvel.length();
+    let min_angle = min_angle * DEGREE_TO_RADIAN_FACTOR;
+    if linvel.y * y > 0.0 && linvel.y.abs() >= speed * min_angle.sin() {
+        return linvel;
+    }
+    let x_sign = if linvel.x < 0.0 { -1.0 } else { 1.0 };
+    let y_sign = if y < 0.0 { -1.0 } else { 1.0 };
+    Vec2::new(x_sign * min_angle.cos(), y_sign * min_angle.sin()) * speed
+}
+
+fn decay_ball_speed(
+    time: Res<Time>,
+    mut ball_query: Query<&mut BallTopSpeed>,
+    game_config: Res<GameConfig>,
+) {
+    for mut ball_top_speed in ball_query.iter_mut() {
+        ball_top_speed.speed = (ball_top_speed.speed
+            - game_config.ball_speed_decay * time.delta_seconds())
+        .max(game_config.ball_speed);
+    }
+}
+
 fn ball_collision(
-    mut commands: Commands,
-    mut ball_query: Query<(Entity, &mut Velocity, &mut BallTopSpeed)>,
-    paddle_query: Query<With<Paddle>>,
-    brick_query: Query<&Brick>,
-    death_wall_query: Query<With<Death>>,
-    mut next_state: ResMut<NextState<AppState>>,
-    mut score: ResMut<Score>,
+    mut ball_query: Query<(Entity, &Transform, &mut Velocity, &mut BallTopSpeed)>,
+    paddle_query: Query<(&Transform, &HorizontalVelocity), With<Paddle>>,
+    brick_query: Query<(Entity, &Brick, &Transform)>,
+    wall_query: Query<(
+        &WallLocation,
+        Has<Death>,
+        Has<Bouncy>,
+        Has<Boost>,
+        Has<Portal>,
+    )>,
     mut collision_events: EventReader<CollisionEvent>,
     mut ball_air_time: ResMut<BallAirTime>,
+    mut gameplay_events: GameplayEventWriters,
     game_config: Res<GameConfig>,
 ) {
-    let Ok((ball_entity, mut ball_velocity, mut ball_top_speed)) = ball_query.get_single_mut()
+    let Ok((ball_entity, ball_transform, mut ball_velocity, mut ball_top_speed)) =
+        ball_query.get_single_mut()
     else {
         return;
     };
 
+    // Despawns are deferred, so bricks destroyed this frame are still in the query
+    let mut destroyed_bricks: Vec<Entity> = Vec::new();
+
     for collision_event in collision_events.read() {
         if let CollisionEvent::Stopped(entity1, entity2, _) = collision_event {
             let entity_pair = if ball_entity == *entity1 {
@@ -149,33 +447,148 @@ fn ball_collision(
                 None
             };
             if let Some((_, other_entity)) = entity_pair {
-                // println!(
-                //     "ball speed: {}, top speed: {}",
-                //     ball_velocity.linvel.length(),
-                //     ball_top_speed.speed
-                // );
-                if let Ok(()) = paddle_query.get(*other_entity) {
-                    ball_velocity.linvel =
-                        ball_velocity.linvel.normalize_or_zero() * ball_top_speed.speed;
+                if let Ok((paddle_transform, paddle_velocity)) = paddle_query.get(*other_entity) {
+                    let offset = (ball_transform.translation.x - paddle_transform.translation.x)
+                        / (game_config.paddle_width / 2.0);
+                    let direction = if game_config.paddle_aim_control {
+                        game_config.get_paddle_bounce_direction(offset, paddle_velocity.value)
+                    } else {
+                        ball_velocity.linvel.normalize_or_zero()
+                    };
+                    ball_velocity.linvel = direction * ball_top_speed.speed;
                     ball_air_time.0 = 0.0;
+                    gameplay_events.paddle_hit.send(PaddleHit {
+                        offset,
+                        speed: ball_top_speed.speed,
+                    });
                     continue;
                 }
-                if let Ok(brick) = brick_query.get(*other_entity) {
-                    score.score += brick.score;
-                    ball_top_speed.speed +=
-                        brick.score as f32 * game_config.score_to_ball_speed_factor;
-                    commands.entity(*other_entity).despawn_recursive();
-                    if brick_query.iter().take(2).count() == 1 {
-                        score.score += game_config.win_score_bonus;
-                        next_state.set(AppState::GaveOver);
+                if let Ok((_, brick, brick_transform)) = brick_query.get(*other_entity) {
+                    if destroyed_bricks.contains(other_entity) {
+                        continue;
+                    }
+                    destroyed_bricks.push(*other_entity);
+                    gameplay_events.brick_destroyed.send(BrickDestroyed {
+                        entity: *other_entity,
+                        score: brick.score,
+                        size: brick.size,
+                        brick_type: brick.brick_type,
+                        position: brick_transform.translation.truncate(),
+                    });
+                    if brick_query.iter().count() == destroyed_bricks.len() {
+                        gameplay_events.field_cleared.send(FieldCleared);
                     }
                     continue;
                 }
-                if let Ok(()) = death_wall_query.get(*other_entity) {
-                    next_state.set(AppState::GaveOver);
+                if let Ok((wall_location, death, bouncy, boost, portal)) =
+                    wall_query.get(*other_entity)
+                {
+                    // Passing through a portal is not a hit
+                    if portal {
+                        continue;
+                    }
+                    gameplay_events.wall_hit.send(WallHit {
+                        location: wall_location.clone(),
+                    });
+                    if death {
+                        gameplay_events.ball_lost.send(BallLost);
+                    }
+                    if bouncy {
+                        ball_velocity.linvel = ball_velocity
+                            .linvel
+                            .clamp_length_max(game_config.ball_max_speed);
+                    }
+                    if boost {
+                        ball_top_speed.speed = (ball_top_speed.speed
+                            + game_config.wall_boost_speed)
+                            .min(game_config.ball_max_speed);
+                        ball_velocity.linvel =
+                            ball_velocity.linvel.normalize_or_zero() * ball_top_speed.speed;
+                    }
                     continue;
                 }
             }
         }
     }
 }
+
+/// Moves the ball to the opposite side of the arena once its middle is past a portal.
+fn wrap_ball(mut ball_query: Query<&mut Transform, With<Ball>>, portal_query: Query<&Portal>) {
+    let Ok(mut ball_transform) = ball_query.get_single_mut() else {
+        return;
+    };
+
+    for portal in portal_query.iter() {
+        let position = ball_transform.translation.truncate();
+        if position.dot(portal.normal) > portal.distance {
+            let wrapped = position - portal.normal * 2.0 * portal.distance;
+            ball_transform.translation.x = wrapped.x;
+            ball_transform.translation.y = wrapped.y;
+        }
+    }
+}
+
+fn speed_up_ball(
+    mut brick_destroyed_events: EventReader<BrickDestroyed>,
+    mut ball_query: Query<&mut BallTopSpeed>,
+    game_config: Res<GameConfig>,
+) {
+    let Ok(mut ball_top_speed) = ball_query.get_single_mut() else {
+        return;
+    };
+
+    for brick_destroyed in brick_destroyed_events.read() {
+        let previous_bonus = game_config
+            .get_ball_speed_bonus(ball_top_speed.score_gained, ball_top_speed.bricks_destroyed);
+        ball_top_speed.score_gained += brick_destroyed.score;
+        ball_top_speed.bricks_destroyed += 1;
+        let bonus = game_config
+            .get_ball_speed_bonus(ball_top_speed.score_gained, ball_top_speed.bricks_destroyed);
+        ball_top_speed.speed =
+            (ball_top_speed.speed + bonus - previous_bonus).min(game_config.ball_max_speed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeating_bounces_are_a_loop() {
+        let (left, right) = (Entity::from_raw(1), Entity::from_raw(2));
+        let bounces: Vec<_> = (0..8)
+            .map(|i| {
+                if i % 2 == 0 {
+                    (left, Vec2::X)
+                } else {
+                    (right, Vec2::NEG_X)
+                }
+            })
+            .collect();
+        assert!(is_bounce_loop(&bounces, 8));
+        assert!(!is_bounce_loop(&bounces[1..], 8));
+    }
+
+    #[test]
+    fn changing_directions_are_not_a_loop() {
+        let (paddle, wall) = (Entity::from_raw(1), Entity::from_raw(2));
+        let bounces: Vec<_> = (0..8)
+            .map(|i| {
+                let entity = if i % 2 == 0 { paddle } else { wall };
+                (entity, Vec2::from_angle(i as f32 * 0.1))
+            })
+            .collect();
+        assert!(!is_bounce_loop(&bounces, 8));
+    }
+
+    #[test]
+    fn nudged_trajectory_stays_off_the_horizontal() {
+        let flat = clamp_trajectory_angle(Vec2::new(-100.0, 1.0), -5.0, 10.0);
+        assert!((flat.length() - 100.0).abs() < 1e-2);
+        assert!(flat.x < 0.0 && flat.y < 0.0);
+        let angle = (flat.y.abs() / flat.length()).asin() / DEGREE_TO_RADIAN_FACTOR;
+        assert!((angle - 10.0).abs() < 1e-3);
+        let steep = Vec2::new(30.0, 80.0);
+        assert_eq!(clamp_trajectory_angle(steep, 1.0, 10.0), steep);
+    }
+}
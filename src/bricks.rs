@@ -1,36 +1,31 @@
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    sprite::MaterialMesh2dBundle,
+};
 use bevy_rapier2d::prelude::*;
 use rand::{
-    distributions::{Uniform, WeightedIndex},
+    distributions::{Uniform, WeightedError, WeightedIndex},
     prelude::*,
 };
 
-use crate::{AppState, GameConfig};
+use crate::{
+    events::{BrickDestroyed, GameplaySet},
+    level::{EditorLevel, Level, LevelBrick},
+    save::SavedRound,
+    show_notification,
+    theme::{Skins, Theme},
+    walls::{get_arena_boundary, get_arena_obstacles, is_inside_arena},
+    AppState, GameConfig,
+};
+
+/// Random spots tried along a candidate edge before it is dropped for not fitting in the arena.
+const PLACEMENT_ATTEMPTS: usize = 8;
 
 // const BRICK_MIN_WIDTH: f32 = 30.0;
 // const BRICK_MAX_WIDTH: f32 = 80.0;
 // const BRICK_WIDTH_STEP: f32 = 10.0;
 // const BRICK_MARGIN: f32 = 3.0;
-const BRICK_COLORS: [Color; 7] = [
-    Color::hsl(0.0, 0.5, 0.5),   // Red
-    Color::hsl(30.0, 0.5, 0.5),  // Orange
-    Color::hsl(60.0, 0.5, 0.5),  // Yellow
-    Color::hsl(120.0, 0.5, 0.5), // Green
-    Color::hsl(240.0, 0.5, 0.5), // Blue
-    Color::hsl(270.0, 0.5, 0.5), // Indigo
-    Color::hsl(290.0, 0.5, 0.5), // Violet
-];
-
-const BRICK_BORDER_COLORS: [Color; 7] = [
-    Color::hsl(0.0, 0.2, 0.3),   // Red
-    Color::hsl(30.0, 0.2, 0.3),  // Orange
-    Color::hsl(60.0, 0.2, 0.3),  // Yellow
-    Color::hsl(120.0, 0.2, 0.3), // Green
-    Color::hsl(240.0, 0.2, 0.3), // Blue
-    Color::hsl(270.0, 0.2, 0.3), // Indigo
-    Color::hsl(290.0, 0.2, 0.3), // Violet
-];
-const BRICK_BORDER_WIDTH: f32 = 5.0;
 // const BOUNDING_BOX: Transform = Transform {
 //     translation: Vec3::new(0.0, 50.0, 0.0),
 //     scale: Vec3::new(990.0, 400.0, 1.0),
@@ -40,31 +35,56 @@ const BRICK_BORDER_WIDTH: f32 = 5.0;
 #[derive(Component, Debug)]
 pub struct Brick {
     pub score: i32,
+    pub size: f32,
+    pub brick_type: usize,
 }
 
+/// Inner sprite of a brick, hidden when the brick is drawn with an image.
+#[derive(Component, Debug)]
+struct BrickFill;
+
+#[derive(Resource, Debug)]
+pub struct LayoutSeed {
+    pub seed: u64,
+}
+
+/// Makes the next generated layout reuse the current `LayoutSeed`.
+#[derive(Resource, Debug)]
+pub struct RetryLayout;
+
 pub struct BrickPlugin;
 
 impl Plugin for BrickPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(AppState::InGame), spawn_bricks);
+        app.add_systems(OnEnter(AppState::InGame), spawn_bricks)
+            .add_systems(OnEnter(AppState::SelectConfig), despawn_bricks)
+            .add_systems(
+                Update,
+                destroy_bricks
+                    .in_set(GameplaySet::Consequences)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(Update, skin_bricks);
     }
 }
 
 fn spawn_bricks(
     mut commands: Commands,
     bricks_query: Query<Entity, With<Brick>>,
+    editor_level: Option<Res<EditorLevel>>,
+    retry_layout: Option<Res<RetryLayout>>,
+    layout_seed: Option<Res<LayoutSeed>>,
+    saved_round: Option<Res<SavedRound>>,
+    theme: Res<Theme>,
     game_config: Res<GameConfig>,
 ) {
     for brick_entity in bricks_query.iter() {
         commands.entity(brick_entity).despawn_recursive();
     }
-
-    let get_brick_color = |side_length: f32, colors: &[Color]| {
-        colors[((side_length - game_config.brick_max_width) / game_config.brick_width_step)
-            .abs()
-            .round() as usize
-            % BRICK_COLORS.len()]
-    };
+    // Resumed rounds respawn their own bricks
+    if saved_round.is_some() {
+        return;
+    }
 
     // commands.spawn(SpriteBundle {
     //     sprite: Sprite {
@@ -75,80 +95,273 @@ fn spawn_bricks(
     //     ..default()
     // });
 
-    for transform in compute_brick_layout(
+    let level = if let Some(editor_level) = editor_level {
+        commands.remove_resource::<LayoutSeed>();
+        editor_level.0.clone()
+    } else if let Some(level_file) = &game_config.level {
+        commands.remove_resource::<LayoutSeed>();
+        match Level::load(&format!("./assets/{level_file}")) {
+            Ok(level) => level,
+            Err(e) => {
+                show_notification("Level File Error", &e);
+                panic!("{e}");
+            }
+        }
+    } else {
+        let seed = retry_layout
+            .and(layout_seed)
+            .map(|layout_seed| layout_seed.seed);
+        generate_level(&mut commands, &game_config, seed)
+    };
+    commands.remove_resource::<RetryLayout>();
+
+    for level_brick in level.bricks.iter() {
+        spawn_brick(&mut commands, level_brick, &theme, &game_config);
+    }
+}
+
+/// Where the bricks of the current round came from, for the score screens.
+pub fn describe_layout(
+    layout_seed: Option<&LayoutSeed>,
+    from_editor: bool,
+    game_config: &GameConfig,
+) -> String {
+    match (layout_seed, from_editor, &game_config.level) {
+        (_, true, _) => "editor level".to_string(),
+        (_, false, Some(level_file)) => format!("level {level_file}"),
+        (Some(layout_seed), false, None) => format!("seed {}", layout_seed.seed),
+        (None, false, None) => "unknown".to_string(),
+    }
+}
+
+fn destroy_bricks(mut commands: Commands, mut brick_destroyed_events: EventReader<BrickDestroyed>) {
+    for brick_destroyed in brick_destroyed_events.read() {
+        commands.entity(brick_destroyed.entity).despawn_recursive();
+    }
+}
+
+fn despawn_bricks(mut commands: Commands, bricks_query: Query<Entity, With<Brick>>) {
+    for brick_entity in bricks_query.iter() {
+        commands.entity(brick_entity).despawn_recursive();
+    }
+}
+
+pub fn generate_level(
+    commands: &mut Commands,
+    game_config: &GameConfig,
+    seed: Option<u64>,
+) -> Level {
+    let seed = seed.unwrap_or_else(|| thread_rng().gen_range(u64::MIN..=u64::MAX));
+    info!("seed: {seed}");
+    commands.insert_resource(LayoutSeed { seed });
+    let layout = compute_brick_layout(
         game_config.get_brick_bounding_box(),
-        &mut commands,
-        &game_config,
-    ) {
-        commands
-            .spawn((
-                SpriteBundle {
-                    sprite: Sprite {
-                        color: get_brick_color(transform.scale.x, &BRICK_BORDER_COLORS),
-                        ..default()
-                    },
-                    transform: Transform {
-                        scale: Vec3 {
-                            x: transform.scale.x - game_config.brick_margin,
-                            y: transform.scale.y - game_config.brick_margin,
-                            z: 1.0,
-                        },
-                        translation: Vec3 {
-                            z: 0.0,
-                            ..transform.translation
-                        },
-                        ..transform
-                    },
+        game_config,
+        seed,
+        None,
+    );
+
+    Level {
+        bricks: layout
+            .squares
+            .iter()
+            .map(|square| LevelBrick::from_square(square, game_config))
+            .collect(),
+    }
+}
+
+pub fn spawn_brick(
+    commands: &mut Commands,
+    level_brick: &LevelBrick,
+    theme: &Theme,
+    game_config: &GameConfig,
+) -> Entity {
+    let size = level_brick.size;
+    commands
+        .spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: theme.brick_border_color(level_brick.brick_type),
                     ..default()
                 },
-                Brick {
-                    score: game_config.get_brick_score(transform.scale.x),
+                transform: Transform {
+                    scale: Vec3 {
+                        x: size - game_config.brick_margin,
+                        y: size - game_config.brick_margin,
+                        z: 1.0,
+                    },
+                    translation: Vec3::new(level_brick.x, level_brick.y, 0.0),
+                    ..default()
                 },
-                RigidBody::Fixed,
-                Collider::cuboid(0.5, 0.5),
-                Friction::coefficient(0.0),
-                Restitution::coefficient(1.0),
-            ))
-            .with_children(|parent| {
-                parent.spawn(SpriteBundle {
+                ..default()
+            },
+            Brick {
+                score: get_brick_type_score(level_brick.brick_type, game_config),
+                size,
+                brick_type: level_brick.brick_type,
+            },
+            RigidBody::Fixed,
+            Collider::cuboid(0.5, 0.5),
+            Friction::coefficient(0.0),
+            Restitution::coefficient(1.0),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                SpriteBundle {
                     sprite: Sprite {
-                        color: get_brick_color(transform.scale.x, &BRICK_COLORS),
+                        color: theme.brick_color(level_brick.brick_type),
                         ..default()
                     },
                     transform: Transform {
                         translation: Vec3::new(0.0, 0.0, 1.0),
                         scale: Vec3 {
-                            x: (transform.scale.x - BRICK_BORDER_WIDTH) / transform.scale.x,
-                            y: (transform.scale.y - BRICK_BORDER_WIDTH) / transform.scale.y,
+                            x: (size - theme.brick_border_width) / size,
+                            y: (size - theme.brick_border_width) / size,
                             z: 1.0,
                         },
                         ..default()
                     },
                     ..default()
-                });
+                },
+                BrickFill,
+            ));
+        })
+        .id()
+}
+
+/// Draws the bricks with the brick image instead of the border and fill sprites.
+fn skin_bricks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut brick_query: Query<(Entity, &Brick, &Transform, &mut Sprite, &Children), Added<Brick>>,
+    mut fill_query: Query<&mut Visibility, With<BrickFill>>,
+    theme: Res<Theme>,
+    skins: Res<Skins>,
+) {
+    let Some(brick_image) = &skins.brick else {
+        return;
+    };
+    for (brick_entity, brick, brick_transform, mut sprite, children) in brick_query.iter_mut() {
+        sprite.color = Color::NONE;
+        let mut fills = fill_query.iter_many_mut(children);
+        while let Some(mut visibility) = fills.fetch_next() {
+            *visibility = Visibility::Hidden;
+        }
+
+        let color = if theme.brick_image_tint {
+            theme.brick_color(brick.brick_type)
+        } else {
+            Color::WHITE
+        };
+        // The brick is scaled to its size, the mesh is laid out on a unit square
+        let border = theme.brick_border_width / brick_transform.scale.x.max(1.0);
+        commands.entity(brick_entity).with_children(|parent| {
+            parent.spawn(MaterialMesh2dBundle {
+                mesh: meshes
+                    .add(nine_slice_mesh(border, theme.brick_image_slice))
+                    .into(),
+                material: materials.add(ColorMaterial {
+                    color,
+                    texture: Some(brick_image.clone()),
+                }),
+                transform: Transform::from_translation(Vec3::new(0.0, 0.0, 1.0)),
+                ..default()
             });
+        });
+    }
+}
+
+/// Unit square split in a 3x3 grid, the corners keep `border` of the square and `slice` of
+/// the image, the edges and center are stretched in between.
+fn nine_slice_mesh(border: f32, slice: f32) -> Mesh {
+    let positions = [-0.5, -0.5 + border.min(0.5), 0.5 - border.min(0.5), 0.5];
+    let uvs = [0.0, slice.min(0.5), 1.0 - slice.min(0.5), 1.0];
+
+    let mut v_pos = Vec::new();
+    let mut v_uv = Vec::new();
+    // From the top left, the images have their origin there
+    for (y, v) in positions.iter().zip(uvs) {
+        for (x, u) in positions.iter().zip(uvs) {
+            v_pos.push(Vec3::new(*x, -y, 0.0));
+            v_uv.push(Vec2::new(u, v));
+        }
+    }
+    let mut indices = Vec::new();
+    for row in 0..3 {
+        for column in 0..3 {
+            let top_left = row * 4 + column;
+            indices.extend([
+                top_left,
+                top_left + 4,
+                top_left + 1,
+                top_left + 1,
+                top_left + 4,
+                top_left + 5,
+            ]);
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, v_pos);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, v_uv);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// Number of distinct brick types, one per width step between the min and max width.
+pub fn get_brick_type_count(game_config: &GameConfig) -> usize {
+    get_brick_color_index(game_config.brick_min_width, game_config) + 1
+}
+
+pub fn get_brick_type_score(brick_type: usize, game_config: &GameConfig) -> i32 {
+    game_config.get_brick_score(
+        game_config.brick_max_width - brick_type as f32 * game_config.brick_width_step,
+    )
+}
+
+pub fn get_brick_color_index(side_length: f32, game_config: &GameConfig) -> usize {
+    ((side_length - game_config.brick_max_width) / game_config.brick_width_step)
+        .abs()
+        .round() as usize
+}
+
+pub fn get_brick_color(side_length: f32, colors: &[Color], game_config: &GameConfig) -> Color {
+    get_brick_type_color(get_brick_color_index(side_length, game_config), colors)
+}
+
+pub fn get_brick_type_color(brick_type: usize, colors: &[Color]) -> Color {
+    colors[brick_type % colors.len()]
+}
+
+pub fn get_edge_debug_color(edge: &Edge, alpha: f32) -> Color {
+    match (&edge.axis, &edge.side) {
+        (Axis::X, Side::POSITIVE) => Color::rgba(1.0, 0.0, 0.0, alpha),
+        (Axis::X, Side::NEGATIVE) => Color::rgba(0.0, 0.0, 1.0, alpha),
+        (Axis::Y, Side::POSITIVE) => Color::rgba(1.0, 1.0, 0.0, alpha),
+        (Axis::Y, Side::NEGATIVE) => Color::rgba(0.0, 1.0, 0.0, alpha),
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-enum Axis {
+pub enum Axis {
     X,
     Y,
 }
 
 #[derive(Debug, Clone, PartialEq)]
-enum Side {
+pub enum Side {
     NEGATIVE,
     POSITIVE,
 }
 
 #[derive(Debug, Clone, PartialEq)]
-struct Edge {
-    start: f32,
-    end: f32,
-    pos: f32,
-    side: Side,
-    axis: Axis,
+pub struct Edge {
+    pub start: f32,
+    pub end: f32,
+    pub pos: f32,
+    pub side: Side,
+    pub axis: Axis,
 }
 impl Edge {
     fn new(a: Vec2, b: Vec2, side: Side) -> Self {
@@ -216,227 +429,273 @@ fn transform_top(rect: &Transform) -> f32 {
     rect.translation.y + rect.scale.y / 2.0
 }
 
-fn compute_brick_layout(
-    bounding_box: Transform,
-    commands: &mut Commands,
-    game_config: &Res<GameConfig>,
-) -> Vec<Transform> {
-    let seed = thread_rng().gen_range(u64::MIN..=u64::MAX);
-    info!("seed: {seed}");
-    let mut rng = StdRng::seed_from_u64(seed);
-
-    let mut max_side_length = game_config.brick_max_width;
-    let side_length_dist_builder = |max_side_length| {
-        Uniform::new_inclusive(
-            (game_config.brick_min_width / game_config.brick_width_step) as i32,
-            (max_side_length / game_config.brick_width_step) as i32,
-        )
-    };
-    let mut side_length_dist = side_length_dist_builder(max_side_length);
-
-    let first_width = side_length_dist.sample(&mut rng) as f32 * game_config.brick_width_step;
-    let first_square = Transform {
-        translation: Vec3 {
-            x: rng.gen_range(
-                transform_left(&bounding_box) + first_width / 2.0
-                    ..=transform_right(&bounding_box) - first_width / 2.0,
-            ),
-            y: rng.gen_range(
-                transform_bottom(&bounding_box) + first_width / 2.0
-                    ..=transform_top(&bounding_box) - first_width / 2.0,
-            ),
-            z: 0.0,
-        },
-        scale: Vec3 {
-            x: first_width,
-            y: first_width,
-            z: 1.0,
-        },
-        ..default()
-    };
-
-    let mut squares = vec![first_square];
-
-    // debug
-    // let debug_brick_y = |width, offset_x: f32, offset_y: f32| Transform {
-    //     translation: Vec3 {
-    //         x: first_square.translation.x + offset_x,
-    //         y: first_square.translation.y + (first_square.scale.y + width) / 2.0 + offset_y,
-    //         z: 0.0,
-    //     },
-    //     scale: Vec3 {
-    //         x: width,
-    //         y: width,
-    //         z: 1.0,
-    //     },
-    //     ..default()
-    // };
-
-    // //debug
-    // let debug_brick_x = |width, offset_x: f32, offset_y: f32| Transform {
-    //     translation: Vec3 {
-    //         x: first_square.translation.x + (first_square.scale.y + width) / 2.0 + offset_x,
-    //         y: first_square.translation.y + offset_y,
-    //         z: 0.0,
-    //     },
-    //     scale: Vec3 {
-    //         x: width,
-    //         y: width,
-    //         z: 1.0,
-    //     },
-    //     ..default()
-    // };
-
-    //debug
-    let mut debug_draw_edges = |edges: &Vec<Edge>, side_length: f32, alpha: f32| {
-        for edge in edges {
-            match edge.axis {
-                Axis::X => {
-                    commands.spawn(SpriteBundle {
-                        sprite: Sprite {
-                            color: match edge.side {
-                                Side::POSITIVE => Color::rgba(1.0, 0.0, 0.0, alpha),
-                                Side::NEGATIVE => Color::rgba(0.0, 0.0, 1.0, alpha),
-                            },
-                            ..default()
-                        },
-                        transform: Transform {
-                            translation: Vec3::new((edge.start + edge.end) / 2.0, edge.pos, 1.0),
-                            scale: Vec3::new(edge.end - edge.start + side_length, side_length, 1.0),
-                            ..default()
-                        },
-                        ..default()
-                    });
-                }
-                Axis::Y => {
-                    commands.spawn(SpriteBundle {
-                        sprite: Sprite {
-                            color: match edge.side {
-                                Side::POSITIVE => Color::rgba(1.0, 1.0, 0.0, alpha),
-                                Side::NEGATIVE => Color::rgba(0.0, 1.0, 0.0, alpha),
-                            },
-                            ..default()
-                        },
-                        transform: Transform {
-                            translation: Vec3::new(edge.pos, (edge.start + edge.end) / 2.0, 1.0),
-                            scale: Vec3::new(side_length, edge.end - edge.start + side_length, 1.0),
-                            ..default()
-                        },
-                        ..default()
-                    });
-                }
-            }
-        }
-    };
+/// State of the packer when it placed a square: the edges it considered, the
+/// sampled side length and the candidate edge the square was placed on.
+#[derive(Debug, Clone)]
+pub struct PackerStep {
+    pub square_side_length: f32,
+    pub positions: Vec<Edge>,
+    pub horizontal_edges: Vec<Edge>,
+    pub vertical_edges: Vec<Edge>,
+    pub trunc_horizontal_edges: Vec<Edge>,
+    pub trunc_vertical_edges: Vec<Edge>,
+    pub chosen_edge: Edge,
+    pub square: Transform,
+}
 
-    let corners = |rect: &Transform| {
-        vec![
-            Vec2::new(transform_left(rect), transform_top(rect)),
-            Vec2::new(transform_right(rect), transform_top(rect)),
-            Vec2::new(transform_right(rect), transform_bottom(rect)),
-            Vec2::new(transform_left(rect), transform_bottom(rect)),
-        ]
-    };
+#[derive(Debug, Clone)]
+pub struct BrickLayout {
+    pub squares: Vec<Transform>,
+    pub debug_step: Option<PackerStep>,
+}
 
-    let mut horizontal_edges: Vec<Edge> = Vec::new();
-    let mut vertical_edges: Vec<Edge> = Vec::new();
-    let limit: Option<i32> = None; //122
-    let mut idx = 0;
-    loop {
-        let square_corners = corners(&squares[squares.len() - 1]);
+pub struct BrickPacker {
+    bounding_box: Transform,
+    brick_min_width: f32,
+    brick_width_step: f32,
+    rng: StdRng,
+    side_length_dist: Uniform<i32>,
+    squares: Vec<Transform>,
+    arena_boundary: Option<Vec<Vec2>>,
+    arena_obstacles: Vec<Transform>,
+    horizontal_edges: Vec<Edge>,
+    vertical_edges: Vec<Edge>,
+    finished: bool,
+}
 
-        let mut edges = square_corners
-            .iter()
-            .zip(square_corners.iter().cycle().skip(1));
-
-        let (a, b) = edges.next().unwrap();
-        horizontal_edges.push(Edge::new(*a, *b, Side::POSITIVE));
-        let (a, b) = edges.next().unwrap();
-        vertical_edges.push(Edge::new(*a, *b, Side::POSITIVE));
-        let (a, b) = edges.next().unwrap();
-        horizontal_edges.push(Edge::new(*a, *b, Side::NEGATIVE));
-        let (a, b) = edges.next().unwrap();
-        vertical_edges.push(Edge::new(*a, *b, Side::NEGATIVE));
-
-        let mut trunc_horizontal_edges = truncate_overlapping_edges(&horizontal_edges);
-        let mut trunc_vertical_edges = truncate_overlapping_edges(&vertical_edges);
-
-        let square_side_length =
-            side_length_dist.sample(&mut rng) as f32 * game_config.brick_width_step;
-
-        let horizontal_positions = get_square_positions(
-            square_side_length,
-            &trunc_horizontal_edges,
-            &mut trunc_vertical_edges,
-        );
-        let vertical_positions = get_square_positions(
-            square_side_length,
-            &trunc_vertical_edges,
-            &mut trunc_horizontal_edges,
-        );
-        let all_positions = truncate_out_of_bounds(
-            square_side_length,
-            &[horizontal_positions, vertical_positions].concat(),
-            &bounding_box,
+impl BrickPacker {
+    pub fn new(bounding_box: Transform, game_config: &GameConfig, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let side_length_dist = Self::side_length_dist(
+            game_config.brick_min_width,
+            game_config.brick_max_width,
+            game_config.brick_width_step,
         );
 
-        if all_positions.len() == 0 {
-            max_side_length = square_side_length - game_config.brick_width_step;
-            if max_side_length < game_config.brick_min_width {
-                break;
-            }
-            side_length_dist = side_length_dist_builder(max_side_length);
-            continue;
+        let arena_boundary = get_arena_boundary(game_config);
+        let arena_obstacles = get_arena_obstacles(game_config);
+
+        let first_square = (0..PLACEMENT_ATTEMPTS)
+            .map(|_| {
+                let first_width =
+                    side_length_dist.sample(&mut rng) as f32 * game_config.brick_width_step;
+                Transform {
+                    translation: Vec3 {
+                        x: rng.gen_range(
+                            transform_left(&bounding_box) + first_width / 2.0
+                                ..=transform_right(&bounding_box) - first_width / 2.0,
+                        ),
+                        y: rng.gen_range(
+                            transform_bottom(&bounding_box) + first_width / 2.0
+                                ..=transform_top(&bounding_box) - first_width / 2.0,
+                        ),
+                        z: 0.0,
+                    },
+                    scale: Vec3 {
+                        x: first_width,
+                        y: first_width,
+                        z: 1.0,
+                    },
+                    ..default()
+                }
+            })
+            .find(|square| is_inside_arena(square, arena_boundary.as_deref(), &arena_obstacles));
+
+        BrickPacker {
+            bounding_box,
+            brick_min_width: game_config.brick_min_width,
+            brick_width_step: game_config.brick_width_step,
+            rng,
+            side_length_dist,
+            finished: first_square.is_none(),
+            squares: first_square.into_iter().collect(),
+            arena_boundary,
+            arena_obstacles,
+            horizontal_edges: Vec::new(),
+            vertical_edges: Vec::new(),
         }
+    }
 
-        if let Some(limit) = limit {
-            if idx == limit {
-                debug_draw_edges(&all_positions, square_side_length, 0.08);
-                debug_draw_edges(&all_positions, 1.0, 1.0);
-                debug_draw_edges(&horizontal_edges, 1.0, 1.0);
-                debug_draw_edges(&vertical_edges, 1.0, 1.0);
-                debug_draw_edges(&trunc_horizontal_edges, 4.0, 1.0);
-                debug_draw_edges(&trunc_vertical_edges, 4.0, 1.0);
-                break;
-            }
-        }
+    fn side_length_dist(min_side_length: f32, max_side_length: f32, step: f32) -> Uniform<i32> {
+        Uniform::new_inclusive(
+            (min_side_length / step) as i32,
+            (max_side_length / step) as i32,
+        )
+    }
 
-        let weights = all_positions.iter().map(|edge| edge.end - edge.start);
+    pub fn squares(&self) -> &Vec<Transform> {
+        &self.squares
+    }
 
-        let weighted_index = match WeightedIndex::new(weights.clone()) {
-            Ok(weighted_index) => weighted_index,
-            Err(e) => panic!("{}\n weights: {:?}", e, weights.collect::<Vec<f32>>()),
-        };
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
 
-        let chosen_edge = &all_positions[weighted_index.sample(&mut rng)];
-        let square_translation = match chosen_edge.axis {
+    fn square_on_edge(&mut self, edge: &Edge, side_length: f32) -> Transform {
+        let translation = match edge.axis {
             Axis::X => Vec3 {
-                x: rng.gen_range(chosen_edge.start..=chosen_edge.end),
-                y: chosen_edge.pos,
+                x: self.rng.gen_range(edge.start..=edge.end),
+                y: edge.pos,
                 z: 0.0,
             },
             Axis::Y => Vec3 {
-                x: chosen_edge.pos,
-                y: rng.gen_range(chosen_edge.start..=chosen_edge.end),
+                x: edge.pos,
+                y: self.rng.gen_range(edge.start..=edge.end),
                 z: 0.0,
             },
         };
-        // squares.push(test_squares[idx]);
-
-        squares.push(Transform {
-            translation: square_translation,
+        Transform {
+            translation,
             scale: Vec3 {
-                x: square_side_length,
-                y: square_side_length,
+                x: side_length,
+                y: side_length,
                 z: 1.0,
             },
             ..default()
-        });
+        }
+    }
+
+    /// Places the next square, returning `None` once no square fits anymore.
+    pub fn step(&mut self) -> Option<PackerStep> {
+        if self.finished {
+            return None;
+        }
 
+        loop {
+            let square_corners = corners(&self.squares[self.squares.len() - 1]);
+
+            let mut edges = square_corners
+                .iter()
+                .zip(square_corners.iter().cycle().skip(1));
+
+            let (a, b) = edges.next().unwrap();
+            self.horizontal_edges
+                .push(Edge::new(*a, *b, Side::POSITIVE));
+            let (a, b) = edges.next().unwrap();
+            self.vertical_edges.push(Edge::new(*a, *b, Side::POSITIVE));
+            let (a, b) = edges.next().unwrap();
+            self.horizontal_edges
+                .push(Edge::new(*a, *b, Side::NEGATIVE));
+            let (a, b) = edges.next().unwrap();
+            self.vertical_edges.push(Edge::new(*a, *b, Side::NEGATIVE));
+
+            let mut trunc_horizontal_edges = truncate_overlapping_edges(&self.horizontal_edges);
+            let mut trunc_vertical_edges = truncate_overlapping_edges(&self.vertical_edges);
+
+            let square_side_length =
+                self.side_length_dist.sample(&mut self.rng) as f32 * self.brick_width_step;
+
+            let horizontal_positions = get_square_positions(
+                square_side_length,
+                &trunc_horizontal_edges,
+                &mut trunc_vertical_edges,
+            );
+            let vertical_positions = get_square_positions(
+                square_side_length,
+                &trunc_vertical_edges,
+                &mut trunc_horizontal_edges,
+            );
+            let all_positions = truncate_out_of_bounds(
+                square_side_length,
+                &[horizontal_positions, vertical_positions].concat(),
+                &self.bounding_box,
+            );
+
+            // Candidates putting the square out of the arena or on an obstacle are dropped
+            let mut candidates = all_positions.clone();
+            let placement = loop {
+                if candidates.is_empty() {
+                    break None;
+                }
+                let weights = candidates.iter().map(|edge| edge.end - edge.start);
+                let weighted_index = match WeightedIndex::new(weights.clone()) {
+                    Ok(weighted_index) => weighted_index,
+                    Err(WeightedError::AllWeightsZero) => break None,
+                    Err(e) => panic!("{}\n weights: {:?}", e, weights.collect::<Vec<f32>>()),
+                };
+                let index = weighted_index.sample(&mut self.rng);
+                let chosen_edge = candidates[index].clone();
+                let mut placed = None;
+                for _ in 0..PLACEMENT_ATTEMPTS {
+                    let square = self.square_on_edge(&chosen_edge, square_side_length);
+                    if is_inside_arena(
+                        &square,
+                        self.arena_boundary.as_deref(),
+                        &self.arena_obstacles,
+                    ) {
+                        placed = Some(square);
+                        break;
+                    }
+                }
+                match placed {
+                    Some(square) => break Some((chosen_edge, square)),
+                    None => {
+                        candidates.swap_remove(index);
+                    }
+                }
+            };
+
+            let Some((chosen_edge, square)) = placement else {
+                let max_side_length = square_side_length - self.brick_width_step;
+                if max_side_length < self.brick_min_width {
+                    self.finished = true;
+                    return None;
+                }
+                self.side_length_dist = Self::side_length_dist(
+                    self.brick_min_width,
+                    max_side_length,
+                    self.brick_width_step,
+                );
+                continue;
+            };
+            self.squares.push(square);
+
+            return Some(PackerStep {
+                square_side_length,
+                positions: all_positions,
+                horizontal_edges: self.horizontal_edges.clone(),
+                vertical_edges: self.vertical_edges.clone(),
+                trunc_horizontal_edges,
+                trunc_vertical_edges,
+                chosen_edge,
+                square,
+            });
+        }
+    }
+}
+
+fn corners(rect: &Transform) -> Vec<Vec2> {
+    vec![
+        Vec2::new(transform_left(rect), transform_top(rect)),
+        Vec2::new(transform_right(rect), transform_top(rect)),
+        Vec2::new(transform_right(rect), transform_bottom(rect)),
+        Vec2::new(transform_left(rect), transform_bottom(rect)),
+    ]
+}
+
+/// Runs the packer to completion. With `limit`, stops after that many steps and
+/// keeps the last step for debugging.
+pub fn compute_brick_layout(
+    bounding_box: Transform,
+    game_config: &GameConfig,
+    seed: u64,
+    limit: Option<i32>,
+) -> BrickLayout {
+    let mut packer = BrickPacker::new(bounding_box, game_config, seed);
+    let mut debug_step = None;
+    let mut idx = 0;
+    while let Some(step) = packer.step() {
+        if limit == Some(idx) {
+            debug_step = Some(step);
+            break;
+        }
         idx += 1;
     }
 
-    squares
+    BrickLayout {
+        squares: packer.squares,
+        debug_step,
+    }
 }
 
 fn truncate_overlapping_edges(edges: &Vec<Edge>) -> Vec<Edge> {
@@ -618,3 +877,36 @@ fn truncate_out_of_bounds(
     }
     trunc_positions
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(game_config: &GameConfig, seed: u64) -> Vec<Transform> {
+        compute_brick_layout(
+            game_config.get_brick_bounding_box(),
+            game_config,
+            seed,
+            None,
+        )
+        .squares
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_layout() {
+        let game_config = GameConfig::parse("assets/default.yaml").unwrap();
+        let first = layout(&game_config, 42);
+        assert!(!first.is_empty());
+        assert_eq!(first, layout(&game_config, 42));
+        assert_ne!(first, layout(&game_config, 43));
+    }
+
+    #[test]
+    fn stepping_the_packer_gives_the_computed_layout() {
+        let game_config = GameConfig::parse("assets/default.yaml").unwrap();
+        let mut packer = BrickPacker::new(game_config.get_brick_bounding_box(), &game_config, 7);
+        while packer.step().is_some() {}
+        assert!(packer.is_finished());
+        assert_eq!(*packer.squares(), layout(&game_config, 7));
+    }
+}
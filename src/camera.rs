@@ -1,16 +1,330 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, render::camera::ScalingMode};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{BallLost, BrickDestroyed, FieldCleared, GameplaySet},
+    settings::{
+        despawn_settings_ui, highlight_settings_buttons, load_settings, save_settings,
+        spawn_settings_button,
+    },
+    theme::Theme,
+    AppState, GameConfig, DEGREE_TO_RADIAN_FACTOR,
+};
+
+const CAMERA_SETTINGS_FILE: &str = "camera.yaml";
+const BRICK_TRAUMA: f32 = 0.25;
+const DEATH_TRAUMA: f32 = 0.8;
+// Trauma lost per second
+const TRAUMA_DECAY: f32 = 1.5;
+const MAX_SHAKE_OFFSET: f32 = 16.0;
+const MAX_SHAKE_ANGLE: f32 = 1.5;
+const SHAKE_FREQUENCY: f32 = 25.0;
+const PULSE_SECONDS: f32 = 0.5;
+const PULSE_ZOOM: f32 = 0.08;
+const FLASH_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.6);
+const SHAKE_STEP: f32 = 0.25;
 
 #[derive(Component, Debug)]
 pub struct MainCamera;
 
+/// Accessibility settings, `shake` scales the screen shake from off to full.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub shake: f32,
+    pub flash: bool,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            shake: 1.0,
+            flash: true,
+        }
+    }
+}
+
+/// Shake grows with the square of the trauma, which decays over time.
+#[derive(Resource, Debug, Default)]
+struct CameraShake {
+    trauma: f32,
+    elapsed: f32,
+}
+
+/// Zoom and flash played when the field is cleared.
+#[derive(Resource, Debug)]
+struct CameraPulse(Timer);
+
+#[derive(Component, Debug)]
+struct ScreenFlash;
+
+#[derive(Component, Debug)]
+struct CameraSettingsUI;
+
+#[derive(Component, Debug)]
+enum CameraButton {
+    DecreaseShake,
+    IncreaseShake,
+    ToggleFlash,
+}
+
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_camera);
+        app.insert_resource(load_settings::<CameraSettings>(CAMERA_SETTINGS_FILE))
+            .insert_resource(CameraShake::default())
+            .add_systems(Startup, spawn_camera)
+            .add_systems(OnEnter(AppState::InGame), fit_camera_to_arena)
+            .add_systems(OnEnter(AppState::Editor), fit_camera_to_arena)
+            .add_systems(OnEnter(AppState::SelectConfig), reset_camera_scaling)
+            .add_systems(OnEnter(AppState::InGame), reset_camera_effects)
+            .add_systems(OnEnter(AppState::SelectConfig), reset_camera_effects)
+            .add_systems(
+                Update,
+                add_camera_effects
+                    .in_set(GameplaySet::Consequences)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(Update, (shake_camera, pulse_camera))
+            .add_systems(OnEnter(AppState::SelectConfig), spawn_camera_settings_ui)
+            .add_systems(
+                OnExit(AppState::SelectConfig),
+                despawn_settings_ui::<CameraSettingsUI>,
+            )
+            .add_systems(
+                Update,
+                (
+                    highlight_settings_buttons::<CameraButton>,
+                    handle_camera_settings_click,
+                    (
+                        despawn_settings_ui::<CameraSettingsUI>,
+                        spawn_camera_settings_ui,
+                    )
+                        .run_if(
+                            resource_changed::<CameraSettings>()
+                                .or_else(resource_changed::<Theme>()),
+                        ),
+                )
+                    .chain()
+                    .run_if(in_state(AppState::SelectConfig)),
+            )
+            .add_systems(Update, save_camera_settings);
     }
 }
 
 fn spawn_camera(mut commands: Commands) {
-    commands.spawn(Camera2dBundle::default());
+    commands.spawn((Camera2dBundle::default(), MainCamera));
+}
+
+/// Scales the projection so the arena fits the window, the spare width or height is left
+/// empty on both sides. Window resizes are followed by the projection itself.
+fn fit_camera_to_arena(
+    mut camera_query: Query<&mut OrthographicProjection, With<MainCamera>>,
+    game_config: Res<GameConfig>,
+) {
+    let arena_size = game_config.get_arena_size();
+    for mut projection in camera_query.iter_mut() {
+        projection.scaling_mode = ScalingMode::AutoMin {
+            min_width: arena_size.x,
+            min_height: arena_size.y,
+        };
+    }
+}
+
+fn reset_camera_scaling(mut camera_query: Query<&mut OrthographicProjection, With<MainCamera>>) {
+    for mut projection in camera_query.iter_mut() {
+        projection.scaling_mode = ScalingMode::WindowSize(1.0);
+    }
+}
+
+fn reset_camera_effects(
+    mut commands: Commands,
+    mut camera_shake: ResMut<CameraShake>,
+    mut camera_query: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+    flash_query: Query<Entity, With<ScreenFlash>>,
+) {
+    *camera_shake = CameraShake::default();
+    commands.remove_resource::<CameraPulse>();
+    for (mut camera_transform, mut projection) in camera_query.iter_mut() {
+        camera_transform.translation.x = 0.0;
+        camera_transform.translation.y = 0.0;
+        camera_transform.rotation = Quat::IDENTITY;
+        projection.scale = 1.0;
+    }
+    for entity in flash_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn add_camera_effects(
+    mut commands: Commands,
+    mut brick_destroyed_events: EventReader<BrickDestroyed>,
+    mut ball_lost_events: EventReader<BallLost>,
+    mut field_cleared_events: EventReader<FieldCleared>,
+    mut camera_shake: ResMut<CameraShake>,
+    camera_settings: Res<CameraSettings>,
+) {
+    let trauma = brick_destroyed_events.read().count() as f32 * BRICK_TRAUMA
+        + ball_lost_events.read().count() as f32 * DEATH_TRAUMA;
+    camera_shake.trauma = (camera_shake.trauma + trauma).min(1.0);
+
+    if field_cleared_events.read().count() == 0 || !camera_settings.flash {
+        return;
+    }
+    commands.insert_resource(CameraPulse(Timer::from_seconds(
+        PULSE_SECONDS,
+        TimerMode::Once,
+    )));
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: FLASH_COLOR.into(),
+            z_index: ZIndex::Global(3),
+            ..default()
+        },
+        ScreenFlash,
+    ));
+}
+
+fn shake_camera(
+    time: Res<Time>,
+    mut camera_shake: ResMut<CameraShake>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+    camera_settings: Res<CameraSettings>,
+) {
+    if camera_shake.trauma <= 0.0 {
+        return;
+    }
+    camera_shake.trauma = (camera_shake.trauma - TRAUMA_DECAY * time.delta_seconds()).max(0.0);
+    camera_shake.elapsed += time.delta_seconds();
+
+    // Out of phase sines give a smooth shake that settles back at the origin
+    let t = camera_shake.elapsed * SHAKE_FREQUENCY;
+    let shake = camera_shake.trauma.powi(2) * camera_settings.shake;
+    for mut camera_transform in camera_query.iter_mut() {
+        camera_transform.translation.x = MAX_SHAKE_OFFSET * shake * t.sin();
+        camera_transform.translation.y = MAX_SHAKE_OFFSET * shake * (t * 1.3 + 1.0).sin();
+        camera_transform.rotation = Quat::from_rotation_z(
+            MAX_SHAKE_ANGLE * DEGREE_TO_RADIAN_FACTOR * shake * (t * 0.7 + 2.0).sin(),
+        );
+    }
+}
+
+fn pulse_camera(
+    mut commands: Commands,
+    time: Res<Time>,
+    camera_pulse: Option<ResMut<CameraPulse>>,
+    mut camera_query: Query<&mut OrthographicProjection, With<MainCamera>>,
+    mut flash_query: Query<(Entity, &mut BackgroundColor), With<ScreenFlash>>,
+) {
+    let Some(mut camera_pulse) = camera_pulse else {
+        return;
+    };
+    camera_pulse.0.tick(time.delta());
+    let left = camera_pulse.0.percent_left();
+
+    for mut projection in camera_query.iter_mut() {
+        projection.scale = 1.0 - PULSE_ZOOM * left;
+    }
+    for (entity, mut background_color) in flash_query.iter_mut() {
+        if camera_pulse.0.finished() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            background_color.0.set_a(FLASH_COLOR.a() * left);
+        }
+    }
+    if camera_pulse.0.finished() {
+        commands.remove_resource::<CameraPulse>();
+    }
+}
+
+fn save_camera_settings(camera_settings: Res<CameraSettings>) {
+    if !camera_settings.is_changed() || camera_settings.is_added() {
+        return;
+    }
+    if let Err(e) = save_settings(CAMERA_SETTINGS_FILE, &*camera_settings) {
+        error!("{e}");
+    }
+}
+
+fn spawn_camera_settings_ui(
+    mut commands: Commands,
+    camera_settings: Res<CameraSettings>,
+    theme: Res<Theme>,
+) {
+    let text_style = TextStyle {
+        font_size: 20.0,
+        color: theme.text,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    display: Display::Grid,
+                    grid_template_columns: RepeatedGridTrack::auto(4),
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(6.0),
+                    row_gap: Val::Px(6.0),
+                    right: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            },
+            CameraSettingsUI,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Screen shake", text_style.clone()));
+            spawn_settings_button(parent, &theme, "-", CameraButton::DecreaseShake);
+            parent.spawn(TextBundle::from_section(
+                if camera_settings.shake > 0.0 {
+                    format!("{:>3.0}%", camera_settings.shake * 100.0)
+                } else {
+                    "Off".to_string()
+                },
+                text_style.clone(),
+            ));
+            spawn_settings_button(parent, &theme, "+", CameraButton::IncreaseShake);
+            spawn_settings_button(
+                parent,
+                &theme,
+                if camera_settings.flash {
+                    "Clear flash: On"
+                } else {
+                    "Clear flash: Off"
+                },
+                CameraButton::ToggleFlash,
+            );
+        });
+}
+
+fn handle_camera_settings_click(
+    button_query: Query<(&CameraButton, &Interaction), Changed<Interaction>>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    for (button, interaction) in button_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            CameraButton::DecreaseShake => {
+                camera_settings.shake = (camera_settings.shake - SHAKE_STEP).max(0.0);
+            }
+            CameraButton::IncreaseShake => {
+                camera_settings.shake = (camera_settings.shake + SHAKE_STEP).min(1.0);
+            }
+            CameraButton::ToggleFlash => {
+                camera_settings.flash = !camera_settings.flash;
+            }
+        }
+    }
 }
@@ -1,14 +1,18 @@
-use crate::{ball::*, paddle::HorizontalVelocity};
+use crate::{ball::*, paddle::HorizontalVelocity, theme::Theme, AppState, GameConfig};
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
 const DEBUG_INTERVAL_SECONDS: f32 = 1.0;
+const DEBUG_HUD_TOGGLE_KEY: KeyCode = KeyCode::F3;
 
 #[derive(Resource, Debug)]
 pub struct DebugTimer {
     timer: Timer,
 }
 
+#[derive(Component, Debug)]
+struct DebugHudText;
+
 pub struct DebugPlugin;
 
 impl Plugin for DebugPlugin {
@@ -47,3 +51,78 @@ fn print_ball_position(
         transform.translation, paddle.value,
     );
 }
+
+/// Toggleable in-game overlay with live ball speed values.
+pub struct DebugHudPlugin;
+
+impl Plugin for DebugHudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::InGame), spawn_debug_hud)
+            .add_systems(
+                Update,
+                (toggle_debug_hud, update_debug_hud).run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+fn spawn_debug_hud(
+    mut commands: Commands,
+    debug_hud_query: Query<With<DebugHudText>>,
+    theme: Res<Theme>,
+) {
+    if !debug_hud_query.is_empty() {
+        return;
+    }
+
+    let mut debug_hud = TextBundle::from_section(
+        "",
+        TextStyle {
+            font_size: 20.0,
+            color: theme.text,
+            ..default()
+        },
+    )
+    .with_style(Style {
+        position_type: PositionType::Absolute,
+        right: Val::Px(5.0),
+        bottom: Val::Px(5.0),
+        ..default()
+    });
+    debug_hud.visibility = Visibility::Hidden;
+    commands.spawn((debug_hud, DebugHudText));
+}
+
+fn toggle_debug_hud(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut debug_hud_query: Query<&mut Visibility, With<DebugHudText>>,
+) {
+    if !keyboard_input.just_pressed(DEBUG_HUD_TOGGLE_KEY) {
+        return;
+    }
+    for mut visibility in debug_hud_query.iter_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+fn update_debug_hud(
+    ball_query: Query<(&Velocity, &BallTopSpeed), With<Ball>>,
+    mut debug_hud_query: Query<&mut Text, With<DebugHudText>>,
+    game_config: Res<GameConfig>,
+) {
+    let Ok((ball_velocity, ball_top_speed)) = ball_query.get_single() else {
+        return;
+    };
+    for mut text in debug_hud_query.iter_mut() {
+        text.sections[0].value = format!(
+            "Speed: {:.0}\nTop speed: {:.0} / {:.0} ({:?})\nBricks destroyed: {}",
+            ball_velocity.linvel.length(),
+            ball_top_speed.speed,
+            game_config.ball_max_speed,
+            game_config.ball_speed_curve,
+            ball_top_speed.bricks_destroyed,
+        );
+    }
+}
@@ -0,0 +1,425 @@
+use bevy::{input::mouse::MouseWheel, prelude::*, window::PrimaryWindow};
+use std::path::Path;
+
+use crate::{
+    bricks::{
+        generate_level, get_brick_color_index, get_brick_type_count, get_brick_type_score,
+        spawn_brick, Brick,
+    },
+    camera::MainCamera,
+    level::{EditorLevel, Level, LevelBrick, LEVELS_DIRECTORY},
+    theme::Theme,
+    AppState, GameConfig,
+};
+
+#[derive(Resource, Debug)]
+struct EditorCursor {
+    size: f32,
+    status: String,
+}
+
+#[derive(Component, Debug)]
+struct EditorText;
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Editor), enter_editor)
+            .add_systems(OnExit(AppState::Editor), exit_editor)
+            .add_systems(
+                Update,
+                (
+                    editor_mouse_input,
+                    editor_keyboard_input,
+                    draw_editor_preview,
+                    update_editor_text,
+                )
+                    .run_if(in_state(AppState::Editor)),
+            )
+            .add_systems(
+                Update,
+                return_to_editor.run_if(
+                    in_state(AppState::GaveOver).and_then(resource_exists::<EditorLevel>()),
+                ),
+            )
+            .add_systems(OnEnter(AppState::SelectConfig), clear_editor_level);
+    }
+}
+
+fn enter_editor(
+    mut commands: Commands,
+    brick_query: Query<Entity, With<Brick>>,
+    editor_level: Option<Res<EditorLevel>>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+    theme: Res<Theme>,
+    game_config: Res<GameConfig>,
+) {
+    for brick_entity in brick_query.iter() {
+        commands.entity(brick_entity).despawn_recursive();
+    }
+
+    let level = match (editor_level, &game_config.level) {
+        (Some(editor_level), _) => editor_level.0.clone(),
+        (None, Some(level_file)) => {
+            Level::load(&format!("./assets/{level_file}")).unwrap_or_else(|e| {
+                warn!("{e}");
+                Level::default()
+            })
+        }
+        (None, None) => Level::default(),
+    };
+    for level_brick in level.bricks.iter() {
+        spawn_brick(&mut commands, level_brick, &theme, &game_config);
+    }
+
+    commands.insert_resource(EditorCursor {
+        size: game_config.brick_max_width,
+        status: String::new(),
+    });
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.0,
+                color: theme.text,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(5.0),
+            bottom: Val::Px(5.0),
+            ..default()
+        }),
+        EditorText,
+    ));
+
+    window_query.single_mut().cursor.visible = true;
+}
+
+fn exit_editor(
+    mut commands: Commands,
+    text_query: Query<Entity, With<EditorText>>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    for entity in text_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<EditorCursor>();
+    window_query.single_mut().cursor.visible = false;
+}
+
+fn clear_editor_level(mut commands: Commands) {
+    commands.remove_resource::<EditorLevel>();
+}
+
+fn return_to_editor(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        next_state.set(AppState::Editor);
+    }
+}
+
+fn cursor_world_position(
+    window_query: &Query<&Window, With<PrimaryWindow>>,
+    camera_query: &Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) -> Option<Vec2> {
+    let (camera, camera_transform) = camera_query.get_single().ok()?;
+    let cursor_position = window_query.get_single().ok()?.cursor_position()?;
+    camera.viewport_to_world_2d(camera_transform, cursor_position)
+}
+
+/// Snaps the bottom left corner of a brick to the width step grid of the
+/// brick bounding box, keeping the brick inside it.
+fn snap_brick_position(position: Vec2, size: f32, game_config: &GameConfig) -> Vec2 {
+    let bounding_box = game_config.get_brick_bounding_box();
+    let min = bounding_box.translation.truncate() - bounding_box.scale.truncate() / 2.0;
+    let max = min + bounding_box.scale.truncate();
+    let step = game_config.brick_width_step;
+
+    let corner = ((position - size / 2.0 - min) / step).round() * step + min;
+    corner.clamp(min, (max - size).max(min)) + size / 2.0
+}
+
+fn brick_fits(
+    position: Vec2,
+    size: f32,
+    ignored: Option<Entity>,
+    brick_query: &Query<(Entity, &Transform, &Brick)>,
+    game_config: &GameConfig,
+) -> bool {
+    let bounding_box = game_config.get_brick_bounding_box();
+    let inside = (position - bounding_box.translation.truncate())
+        .abs()
+        .cmple((bounding_box.scale.truncate() - size) / 2.0 + 0.01)
+        .all();
+
+    inside
+        && brick_query
+            .iter()
+            .filter(|(entity, _, _)| Some(*entity) != ignored)
+            .all(|(_, transform, brick)| {
+                (position - transform.translation.truncate())
+                    .abs()
+                    .cmpge(Vec2::splat((size + brick.size) / 2.0 - 0.01))
+                    .any()
+            })
+}
+
+fn hovered_brick<'a>(
+    position: Vec2,
+    brick_query: &'a Query<(Entity, &Transform, &Brick)>,
+) -> Option<(Entity, &'a Transform, &'a Brick)> {
+    brick_query.iter().find(|(_, transform, brick)| {
+        (position - transform.translation.truncate())
+            .abs()
+            .cmple(Vec2::splat(brick.size / 2.0))
+            .all()
+    })
+}
+
+fn editor_mouse_input(
+    mut commands: Commands,
+    mouse_input: Res<Input<MouseButton>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    brick_query: Query<(Entity, &Transform, &Brick)>,
+    mut editor_cursor: ResMut<EditorCursor>,
+    theme: Res<Theme>,
+    game_config: Res<GameConfig>,
+) {
+    let scroll: f32 = mouse_wheel.read().map(|event| event.y.signum()).sum();
+    let Some(position) = cursor_world_position(&window_query, &camera_query) else {
+        return;
+    };
+    let hovered = hovered_brick(position, &brick_query);
+
+    if scroll != 0.0 {
+        let step = game_config.brick_width_step * scroll;
+        match hovered {
+            Some((entity, transform, brick)) => {
+                // Resize around the bottom left corner so the brick stays on the grid
+                let size = (brick.size + step)
+                    .clamp(game_config.brick_min_width, game_config.brick_max_width);
+                let corner = transform.translation.truncate() - brick.size / 2.0;
+                let new_position = snap_brick_position(corner + size / 2.0, size, &game_config);
+                if brick_fits(new_position, size, Some(entity), &brick_query, &game_config) {
+                    commands.entity(entity).despawn_recursive();
+                    spawn_brick(
+                        &mut commands,
+                        &LevelBrick {
+                            x: new_position.x,
+                            y: new_position.y,
+                            size,
+                            brick_type: get_brick_color_index(size, &game_config),
+                        },
+                        &theme,
+                        &game_config,
+                    );
+                } else {
+                    editor_cursor.status = "Not enough room to resize".to_string();
+                }
+            }
+            None => {
+                editor_cursor.size = (editor_cursor.size + step)
+                    .clamp(game_config.brick_min_width, game_config.brick_max_width);
+            }
+        }
+    }
+
+    if mouse_input.just_pressed(MouseButton::Left) && hovered.is_none() {
+        let size = editor_cursor.size;
+        let position = snap_brick_position(position, size, &game_config);
+        if brick_fits(position, size, None, &brick_query, &game_config) {
+            spawn_brick(
+                &mut commands,
+                &LevelBrick {
+                    x: position.x,
+                    y: position.y,
+                    size,
+                    brick_type: get_brick_color_index(size, &game_config),
+                },
+                &theme,
+                &game_config,
+            );
+        }
+    }
+
+    if mouse_input.just_pressed(MouseButton::Right) {
+        if let Some((entity, _, _)) = hovered {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn collect_level(brick_query: &Query<(Entity, &Transform, &Brick)>) -> Level {
+    Level {
+        bricks: brick_query
+            .iter()
+            .map(|(_, transform, brick)| LevelBrick {
+                x: transform.translation.x,
+                y: transform.translation.y,
+                size: brick.size,
+                brick_type: brick.brick_type,
+            })
+            .collect(),
+    }
+}
+
+fn editor_keyboard_input(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    brick_query: Query<(Entity, &Transform, &Brick)>,
+    mut editor_cursor: ResMut<EditorCursor>,
+    mut next_state: ResMut<NextState<AppState>>,
+    theme: Res<Theme>,
+    game_config: Res<GameConfig>,
+) {
+    if keyboard_input.just_pressed(KeyCode::T) {
+        let hovered = cursor_world_position(&window_query, &camera_query)
+            .and_then(|position| hovered_brick(position, &brick_query));
+        if let Some((entity, transform, brick)) = hovered {
+            commands.entity(entity).despawn_recursive();
+            spawn_brick(
+                &mut commands,
+                &LevelBrick {
+                    x: transform.translation.x,
+                    y: transform.translation.y,
+                    size: brick.size,
+                    brick_type: (brick.brick_type + 1) % get_brick_type_count(&game_config),
+                },
+                &theme,
+                &game_config,
+            );
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::G) || keyboard_input.just_pressed(KeyCode::C) {
+        for (entity, _, _) in brick_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        if keyboard_input.just_pressed(KeyCode::G) {
+            let level = generate_level(&mut commands, &game_config, None);
+            for level_brick in level.bricks.iter() {
+                spawn_brick(&mut commands, level_brick, &theme, &game_config);
+            }
+            editor_cursor.status = format!("Generated {} bricks", level.bricks.len());
+        } else {
+            editor_cursor.status = "Cleared".to_string();
+        }
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::P) {
+        if brick_query.is_empty() {
+            editor_cursor.status = "Place at least one brick to play".to_string();
+            return;
+        }
+        commands.insert_resource(EditorLevel(collect_level(&brick_query)));
+        next_state.set(AppState::InGame);
+        return;
+    }
+
+    let pressed_control = keyboard_input.pressed(KeyCode::ControlLeft)
+        || keyboard_input.pressed(KeyCode::ControlRight);
+    if pressed_control && keyboard_input.just_pressed(KeyCode::S) {
+        let level = collect_level(&brick_query);
+        let config_name = Path::new(&game_config.file_name)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "level".to_string());
+        let file_path = format!("{LEVELS_DIRECTORY}/{config_name}.yaml");
+        editor_cursor.status = match level.save(&file_path) {
+            Ok(()) => {
+                info!("saved level to {file_path}");
+                format!("Saved to {file_path}, set 'level: levels/{config_name}.yaml' to play it")
+            }
+            Err(e) => e,
+        };
+        commands.insert_resource(EditorLevel(level));
+    }
+}
+
+fn draw_editor_preview(
+    mut gizmos: Gizmos,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    brick_query: Query<(Entity, &Transform, &Brick)>,
+    editor_cursor: Res<EditorCursor>,
+    theme: Res<Theme>,
+    game_config: Res<GameConfig>,
+) {
+    let bounding_box = game_config.get_brick_bounding_box();
+    gizmos.rect_2d(
+        bounding_box.translation.truncate(),
+        0.0,
+        bounding_box.scale.truncate(),
+        theme.editor_bounds,
+    );
+
+    let Some(position) = cursor_world_position(&window_query, &camera_query) else {
+        return;
+    };
+
+    if let Some((_, transform, brick)) = hovered_brick(position, &brick_query) {
+        gizmos.rect_2d(
+            transform.translation.truncate(),
+            0.0,
+            Vec2::splat(brick.size),
+            theme.editor_hover,
+        );
+        return;
+    }
+
+    let size = editor_cursor.size;
+    let snapped_position = snap_brick_position(position, size, &game_config);
+    gizmos.rect_2d(
+        snapped_position,
+        0.0,
+        Vec2::splat(size - game_config.brick_margin),
+        if brick_fits(snapped_position, size, None, &brick_query, &game_config) {
+            theme.editor_valid
+        } else {
+            theme.editor_invalid
+        },
+    );
+}
+
+fn update_editor_text(
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    brick_query: Query<(Entity, &Transform, &Brick)>,
+    mut text_query: Query<&mut Text, With<EditorText>>,
+    editor_cursor: Res<EditorCursor>,
+    game_config: Res<GameConfig>,
+) {
+    let hovered = cursor_world_position(&window_query, &camera_query)
+        .and_then(|position| hovered_brick(position, &brick_query))
+        .map(|(_, _, brick)| {
+            format!(
+                ", hovered: size {} type {} score {}",
+                brick.size,
+                brick.brick_type,
+                get_brick_type_score(brick.brick_type, &game_config)
+            )
+        })
+        .unwrap_or_default();
+
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = format!(
+            "Editor - {} bricks, brush size: {}{}\n{}\n\
+             [Left click] place  [Right click] delete  [Wheel] resize  [T] retype\n\
+             [G] generate  [C] clear  [P] play  [Ctrl+S] save  [Tab] back to editor after a game",
+            brick_query.iter().count(),
+            editor_cursor.size,
+            hovered,
+            editor_cursor.status,
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy_rapier2d::prelude::*;
+use rand::prelude::*;
+
+use crate::{
+    ball::{Ball, BallTopSpeed},
+    events::{BrickDestroyed, GameplaySet, PaddleHit, WallHit},
+    paddle::Paddle,
+    theme::Theme,
+    walls::WallLocation,
+    AppState, GameConfig,
+};
+
+const SHARD_SIZE_RATIO: f32 = 0.2;
+const SHARD_MIN_COUNT: i32 = 4;
+const SHARD_MAX_COUNT: i32 = 12;
+const SHARD_MIN_SPEED: f32 = 80.0;
+const SHARD_MAX_SPEED: f32 = 320.0;
+const SHARD_GRAVITY: f32 = -900.0;
+const SHARD_SECONDS: f32 = 0.6;
+const SQUASH_AMOUNT: f32 = 0.25;
+const SQUASH_SECONDS: f32 = 0.15;
+const TRAIL_INTERVAL: f32 = 0.02;
+const TRAIL_MIN_SECONDS: f32 = 0.08;
+const TRAIL_MAX_SECONDS: f32 = 0.35;
+const TRAIL_MIN_ALPHA: f32 = 0.1;
+const TRAIL_MAX_ALPHA: f32 = 0.5;
+
+/// Short lived visual, moved, shrunk and faded until its lifetime runs out.
+#[derive(Component, Debug)]
+struct Particle {
+    velocity: Vec2,
+    gravity: f32,
+    size: f32,
+    alpha: f32,
+    lifetime: Timer,
+}
+
+/// Visual child of the ball or paddle, stretched for a moment after an impact.
+#[derive(Component, Debug, Default)]
+pub struct Squash {
+    timer: Timer,
+    // Squashed along x instead of y
+    horizontal: bool,
+}
+
+impl Squash {
+    fn start(&mut self, horizontal: bool) {
+        self.timer = Timer::from_seconds(SQUASH_SECONDS, TimerMode::Once);
+        self.horizontal = horizontal;
+    }
+}
+
+#[derive(Resource, Debug)]
+struct BallTrail {
+    mesh: Handle<Mesh>,
+    timer: Timer,
+}
+
+pub struct EffectsPlugin;
+
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_ball_trail)
+            .add_systems(OnEnter(AppState::InGame), despawn_particles)
+            .add_systems(OnEnter(AppState::SelectConfig), despawn_particles)
+            .add_systems(
+                Update,
+                (spawn_brick_shards, squash_on_impact)
+                    .in_set(GameplaySet::Consequences)
+                    .run_if(in_state(AppState::InGame).and_then(effects_enabled)),
+            )
+            .add_systems(
+                Update,
+                spawn_ball_trail.run_if(in_state(AppState::InGame).and_then(effects_enabled)),
+            )
+            .add_systems(Update, (update_particles, animate_squash));
+    }
+}
+
+fn effects_enabled(game_config: Option<Res<GameConfig>>) -> bool {
+    game_config.is_some_and(|game_config| game_config.effects)
+}
+
+fn setup_ball_trail(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.insert_resource(BallTrail {
+        mesh: meshes.add(shape::Circle::default().into()),
+        timer: Timer::from_seconds(TRAIL_INTERVAL, TimerMode::Repeating),
+    });
+}
+
+fn despawn_particles(mut commands: Commands, particle_query: Query<Entity, With<Particle>>) {
+    for particle_entity in particle_query.iter() {
+        commands.entity(particle_entity).despawn_recursive();
+    }
+}
+
+fn spawn_brick_shards(
+    mut commands: Commands,
+    mut brick_destroyed_events: EventReader<BrickDestroyed>,
+    theme: Res<Theme>,
+    game_config: Res<GameConfig>,
+) {
+    let mut rng = thread_rng();
+    for brick_destroyed in brick_destroyed_events.read() {
+        let color = theme.brick_color(brick_destroyed.brick_type);
+        // Bigger bricks break into more pieces
+        let count = ((brick_destroyed.size / game_config.brick_min_width.max(1.0)
+            * SHARD_MIN_COUNT as f32)
+            .round() as i32)
+            .clamp(SHARD_MIN_COUNT, SHARD_MAX_COUNT);
+        let size = brick_destroyed.size * SHARD_SIZE_RATIO;
+        for _ in 0..count {
+            let direction = Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU));
+            let offset = direction * rng.gen_range(0.0..brick_destroyed.size / 3.0);
+            commands.spawn((
+                Particle {
+                    velocity: direction * rng.gen_range(SHARD_MIN_SPEED..SHARD_MAX_SPEED),
+                    gravity: SHARD_GRAVITY,
+                    size,
+                    alpha: 1.0,
+                    lifetime: Timer::from_seconds(SHARD_SECONDS, TimerMode::Once),
+                },
+                SpriteBundle {
+                    sprite: Sprite { color, ..default() },
+                    transform: Transform {
+                        translation: (brick_destroyed.position + offset).extend(5.0),
+                        rotation: Quat::from_rotation_z(rng.gen_range(0.0..std::f32::consts::PI)),
+                        scale: Vec3::new(size, size, 1.0),
+                    },
+                    ..default()
+                },
+            ));
+        }
+    }
+}
+
+fn squash_on_impact(
+    mut paddle_hit_events: EventReader<PaddleHit>,
+    mut wall_hit_events: EventReader<WallHit>,
+    mut brick_destroyed_events: EventReader<BrickDestroyed>,
+    ball_query: Query<(&Children, &Velocity), With<Ball>>,
+    paddle_query: Query<&Children, With<Paddle>>,
+    mut squash_query: Query<&mut Squash>,
+) {
+    let Ok((ball_children, ball_velocity)) = ball_query.get_single() else {
+        return;
+    };
+    let mut ball_squash = None;
+
+    if paddle_hit_events.read().count() > 0 {
+        for paddle_children in paddle_query.iter() {
+            let mut squashes = squash_query.iter_many_mut(paddle_children);
+            while let Some(mut squash) = squashes.fetch_next() {
+                squash.start(false);
+            }
+        }
+        ball_squash = Some(false);
+    }
+    for wall_hit in wall_hit_events.read() {
+        ball_squash = Some(matches!(
+            wall_hit.location,
+            WallLocation::Left | WallLocation::Right
+        ));
+    }
+    if brick_destroyed_events.read().count() > 0 {
+        // The side of the brick that was hit is unknown, the bounce has already turned the
+        // ball away along the axis it hit
+        ball_squash = Some(ball_velocity.linvel.x.abs() > ball_velocity.linvel.y.abs());
+    }
+
+    if let Some(horizontal) = ball_squash {
+        let mut squashes = squash_query.iter_many_mut(ball_children);
+        while let Some(mut squash) = squashes.fetch_next() {
+            squash.start(horizontal);
+        }
+    }
+}
+
+fn animate_squash(time: Res<Time>, mut squash_query: Query<(&mut Transform, &mut Squash)>) {
+    for (mut transform, mut squash) in squash_query.iter_mut() {
+        squash.timer.tick(time.delta());
+        let amount = SQUASH_AMOUNT * squash.timer.percent_left();
+        let (x, y) = if squash.horizontal {
+            (1.0 - amount, 1.0 + amount)
+        } else {
+            (1.0 + amount, 1.0 - amount)
+        };
+        transform.scale.x = x;
+        transform.scale.y = y;
+    }
+}
+
+fn spawn_ball_trail(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut ball_trail: ResMut<BallTrail>,
+    ball_query: Query<(&Transform, &Velocity, &BallTopSpeed), With<Ball>>,
+    theme: Res<Theme>,
+    game_config: Res<GameConfig>,
+) {
+    if !ball_trail.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Ok((ball_transform, ball_velocity, ball_top_speed)) = ball_query.get_single() else {
+        return;
+    };
+    // No trail while the ball rides the paddle
+    if ball_velocity.linvel.length() < 1.0 {
+        return;
+    }
+
+    let speed_ratio = ((ball_top_speed.speed - game_config.ball_speed)
+        / (game_config.ball_max_speed - game_config.ball_speed).max(1.0))
+    .clamp(0.0, 1.0);
+    let alpha = TRAIL_MIN_ALPHA + speed_ratio * (TRAIL_MAX_ALPHA - TRAIL_MIN_ALPHA);
+    commands.spawn((
+        Particle {
+            velocity: Vec2::ZERO,
+            gravity: 0.0,
+            size: game_config.ball_diameter,
+            alpha,
+            lifetime: Timer::from_seconds(
+                TRAIL_MIN_SECONDS + speed_ratio * (TRAIL_MAX_SECONDS - TRAIL_MIN_SECONDS),
+                TimerMode::Once,
+            ),
+        },
+        MaterialMesh2dBundle {
+            mesh: ball_trail.mesh.clone().into(),
+            material: materials.add(ColorMaterial::from(theme.ball.with_a(alpha))),
+            transform: Transform {
+                translation: ball_transform.translation.truncate().extend(0.5),
+                scale: Vec3::new(game_config.ball_diameter, game_config.ball_diameter, 1.0),
+                ..default()
+            },
+            ..default()
+        },
+    ));
+}
+
+fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut particle_query: Query<(
+        Entity,
+        &mut Transform,
+        &mut Particle,
+        Option<&mut Sprite>,
+        Option<&Handle<ColorMaterial>>,
+    )>,
+) {
+    for (particle_entity, mut transform, mut particle, sprite, material) in
+        particle_query.iter_mut()
+    {
+        if particle.lifetime.tick(time.delta()).finished() {
+            commands.entity(particle_entity).despawn_recursive();
+            continue;
+        }
+
+        particle.velocity.y += particle.gravity * time.delta_seconds();
+        transform.translation += (particle.velocity * time.delta_seconds()).extend(0.0);
+        let left = particle.lifetime.percent_left();
+        transform.scale.x = particle.size * left;
+        transform.scale.y = particle.size * left;
+
+        let alpha = particle.alpha * left;
+        if let Some(mut sprite) = sprite {
+            sprite.color.set_a(alpha);
+        }
+        if let Some(material) = material.and_then(|material| materials.get_mut(material)) {
+            material.color.set_a(alpha);
+        }
+    }
+}
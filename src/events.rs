@@ -0,0 +1,67 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{layout_debug::LayoutDebugger, walls::WallLocation};
+
+#[derive(Event, Debug, Clone)]
+pub struct BrickDestroyed {
+    pub entity: Entity,
+    // Brick score before the combo multiplier
+    pub score: i32,
+    pub size: f32,
+    pub brick_type: usize,
+    pub position: Vec2,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct PaddleHit {
+    // -1.0 on the left end of the paddle, 1.0 on the right end
+    pub offset: f32,
+    pub speed: f32,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct WallHit {
+    pub location: WallLocation,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct BallLost;
+
+#[derive(Event, Debug, Clone)]
+pub struct FieldCleared;
+
+/// Collisions are turned into events first, the systems reacting to them run after in the
+/// same frame, before a round ending event changes the state.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameplaySet {
+    Collisions,
+    Consequences,
+}
+
+/// Writers for every gameplay event, so emitters take them as a single system param.
+#[derive(SystemParam)]
+pub struct GameplayEventWriters<'w> {
+    pub brick_destroyed: EventWriter<'w, BrickDestroyed>,
+    pub paddle_hit: EventWriter<'w, PaddleHit>,
+    pub wall_hit: EventWriter<'w, WallHit>,
+    pub ball_lost: EventWriter<'w, BallLost>,
+    pub field_cleared: EventWriter<'w, FieldCleared>,
+}
+
+pub struct GameplayEventsPlugin;
+
+impl Plugin for GameplayEventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BrickDestroyed>()
+            .add_event::<PaddleHit>()
+            .add_event::<WallHit>()
+            .add_event::<BallLost>()
+            .add_event::<FieldCleared>()
+            .configure_sets(
+                Update,
+                (GameplaySet::Collisions, GameplaySet::Consequences)
+                    .chain()
+                    .run_if(not(resource_exists::<LayoutDebugger>())),
+            );
+    }
+}
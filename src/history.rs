@@ -0,0 +1,370 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+use std::{fmt::Write, fs};
+
+use crate::{
+    bricks::{describe_layout, LayoutSeed},
+    level::EditorLevel,
+    score::Score,
+    stats::{csv_field, session_start_time, RoundStats, DATA_DIRECTORY},
+    theme::Theme,
+    AppState, GameConfig,
+};
+
+const HISTORY_TOGGLE_KEY: KeyCode = KeyCode::H;
+const HISTORY_EXPORT_KEY: KeyCode = KeyCode::E;
+const HISTORY_LINE_HEIGHT: f32 = 20.0;
+
+#[derive(Debug, Clone)]
+pub struct GameRecord {
+    pub score: i32,
+    pub won: bool,
+    pub config: String,
+    pub layout: String,
+    pub duration: f32,
+}
+
+/// Every game played since the app started, across configs.
+#[derive(Resource, Debug)]
+pub struct SessionHistory {
+    pub started: u64,
+    pub games: Vec<GameRecord>,
+}
+
+impl SessionHistory {
+    pub fn best_score(&self) -> Option<i32> {
+        self.games.iter().map(|game| game.score).max()
+    }
+
+    pub fn average_score(&self) -> f32 {
+        self.games.iter().map(|game| game.score as f32).sum::<f32>() / self.games.len() as f32
+    }
+
+    pub fn average_duration(&self) -> f32 {
+        self.games.iter().map(|game| game.duration).sum::<f32>() / self.games.len() as f32
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("game,score,won,config,layout,duration_seconds\n");
+        for (index, game) in self.games.iter().enumerate() {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{:.2}",
+                index + 1,
+                game.score,
+                game.won,
+                csv_field(&game.config),
+                csv_field(&game.layout),
+                game.duration,
+            );
+        }
+        csv
+    }
+}
+
+pub fn format_duration(seconds: f32) -> String {
+    let seconds = seconds as i32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[derive(Resource, Debug)]
+struct HistoryPanel {
+    visible: bool,
+    scroll: f32,
+    export_status: Option<String>,
+}
+
+#[derive(Component, Debug)]
+struct HistoryPanelNode;
+
+#[derive(Component, Debug)]
+struct HistoryList;
+
+#[derive(Component, Debug)]
+struct HistorySummaryText;
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SessionHistory {
+            started: session_start_time(),
+            games: Vec::new(),
+        })
+        .insert_resource(HistoryPanel {
+            visible: true,
+            scroll: 0.0,
+            export_status: None,
+        })
+        .add_systems(OnEnter(AppState::GaveOver), record_game)
+        .add_systems(
+            Update,
+            (toggle_history_panel, export_history)
+                .run_if(in_state(AppState::InGame).or_else(in_state(AppState::GaveOver))),
+        )
+        // Rebuilt in the colors of the new theme whenever it changes
+        .add_systems(
+            Update,
+            (
+                despawn_history_panel,
+                spawn_history_panel,
+                apply_deferred,
+                rebuild_history_list,
+            )
+                .chain()
+                .run_if(resource_changed::<Theme>()),
+        )
+        .add_systems(
+            Update,
+            rebuild_history_list.run_if(resource_changed::<SessionHistory>()),
+        )
+        .add_systems(Update, (update_history_panel, scroll_history_list).chain());
+    }
+}
+
+fn despawn_history_panel(
+    mut commands: Commands,
+    panel_query: Query<Entity, With<HistoryPanelNode>>,
+) {
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn spawn_history_panel(mut commands: Commands, theme: Res<Theme>) {
+    let mut history_panel = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            right: Val::Px(5.0),
+            top: Val::Px(5.0),
+            width: Val::Px(360.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            row_gap: Val::Px(6.0),
+            ..default()
+        },
+        background_color: theme.panel.into(),
+        ..default()
+    };
+    history_panel.visibility = Visibility::Hidden;
+
+    commands
+        .spawn((history_panel, HistoryPanelNode))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Session history  [H] hide  [E] export CSV",
+                TextStyle {
+                    font_size: 18.0,
+                    color: theme.text,
+                    ..default()
+                },
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        max_height: Val::Vh(40.0),
+                        overflow: Overflow::clip_y(),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Column,
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        HistoryList,
+                    ));
+                });
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 18.0,
+                        color: theme.text,
+                        ..default()
+                    },
+                ),
+                HistorySummaryText,
+            ));
+        });
+}
+
+fn record_game(
+    mut session_history: ResMut<SessionHistory>,
+    score: Res<Score>,
+    round_stats: Res<RoundStats>,
+    layout_seed: Option<Res<LayoutSeed>>,
+    editor_level: Option<Res<EditorLevel>>,
+    game_config: Res<GameConfig>,
+) {
+    session_history.games.push(GameRecord {
+        score: score.score,
+        won: round_stats.won,
+        config: game_config.file_name.clone(),
+        layout: describe_layout(layout_seed.as_deref(), editor_level.is_some(), &game_config),
+        duration: round_stats.time_played,
+    });
+}
+
+fn toggle_history_panel(
+    mut history_panel: ResMut<HistoryPanel>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    if keyboard_input.just_pressed(HISTORY_TOGGLE_KEY) {
+        history_panel.visible = !history_panel.visible;
+    }
+}
+
+fn export_history(
+    mut history_panel: ResMut<HistoryPanel>,
+    session_history: Res<SessionHistory>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    if !history_panel.visible
+        || session_history.games.is_empty()
+        || !keyboard_input.just_pressed(HISTORY_EXPORT_KEY)
+    {
+        return;
+    }
+
+    // One file per session, exporting again overwrites it with the new games
+    let file_path = format!(
+        "{DATA_DIRECTORY}/session_history_{}.csv",
+        session_history.started
+    );
+    let result = fs::create_dir_all(DATA_DIRECTORY)
+        .and_then(|()| fs::write(&file_path, session_history.to_csv()));
+    history_panel.export_status = Some(match result {
+        Ok(()) => {
+            info!("exported session history to {file_path}");
+            format!("Exported to {file_path}")
+        }
+        Err(e) => {
+            error!("failed to write {file_path}: {e}");
+            format!("Failed to write {file_path}: {e}")
+        }
+    });
+}
+
+fn rebuild_history_list(
+    mut commands: Commands,
+    session_history: Res<SessionHistory>,
+    list_query: Query<Entity, With<HistoryList>>,
+    theme: Res<Theme>,
+) {
+    let Ok(list_entity) = list_query.get_single() else {
+        return;
+    };
+
+    commands.entity(list_entity).despawn_descendants();
+    commands.entity(list_entity).with_children(|parent| {
+        // Newest game first so the last result is visible without scrolling
+        for (index, game) in session_history.games.iter().enumerate().rev() {
+            parent.spawn(
+                TextBundle::from_section(
+                    format!(
+                        "Game {}: {}  {}  {}  {}",
+                        index + 1,
+                        game.score,
+                        game.config,
+                        game.layout,
+                        format_duration(game.duration),
+                    ),
+                    TextStyle {
+                        font_size: 16.0,
+                        color: if game.won {
+                            theme.text_success
+                        } else {
+                            theme.text
+                        },
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    min_height: Val::Px(HISTORY_LINE_HEIGHT),
+                    ..default()
+                }),
+            );
+        }
+    });
+}
+
+fn update_history_panel(
+    history_panel: Res<HistoryPanel>,
+    session_history: Res<SessionHistory>,
+    state: Res<State<AppState>>,
+    mut panel_query: Query<&mut Visibility, With<HistoryPanelNode>>,
+    mut summary_query: Query<&mut Text, With<HistorySummaryText>>,
+) {
+    let visible = history_panel.visible
+        && !session_history.games.is_empty()
+        && matches!(state.get(), AppState::InGame | AppState::GaveOver);
+    for mut visibility in panel_query.iter_mut() {
+        *visibility = if visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+
+    if !visible {
+        return;
+    }
+    for mut text in summary_query.iter_mut() {
+        // A panel respawned for a new theme starts with an empty summary
+        if !(history_panel.is_changed()
+            || session_history.is_changed()
+            || text.sections[0].value.is_empty())
+        {
+            continue;
+        }
+        text.sections[0].value = format!(
+            "Games: {}, best: {}, average: {:.0}, average time: {}",
+            session_history.games.len(),
+            session_history.best_score().unwrap_or(0),
+            session_history.average_score(),
+            format_duration(session_history.average_duration()),
+        );
+        if let Some(export_status) = &history_panel.export_status {
+            text.sections[0].value += &format!("\n{export_status}");
+        }
+    }
+}
+
+fn scroll_history_list(
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut history_panel: ResMut<HistoryPanel>,
+    mut list_query: Query<(&mut Style, &Node, &Parent), With<HistoryList>>,
+    node_query: Query<&Node>,
+) {
+    let Ok((mut style, list_node, parent)) = list_query.get_single_mut() else {
+        return;
+    };
+
+    let dy: f32 = mouse_wheel
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y * HISTORY_LINE_HEIGHT,
+            MouseScrollUnit::Pixel => event.y,
+        })
+        .sum();
+    if dy == 0.0 || !history_panel.visible {
+        return;
+    }
+
+    let container_height = node_query
+        .get(parent.get())
+        .map_or(0.0, |node| node.size().y);
+    let max_scroll = (list_node.size().y - container_height).max(0.0);
+    history_panel.scroll = (history_panel.scroll + dy).clamp(-max_scroll, 0.0);
+    style.top = Val::Px(history_panel.scroll);
+}
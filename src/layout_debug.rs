@@ -0,0 +1,260 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::prelude::*;
+
+use crate::{
+    bricks::{
+        get_brick_color, get_edge_debug_color, Axis, Brick, BrickPacker, Edge, LayoutSeed,
+        PackerStep,
+    },
+    theme::Theme,
+    AppState, GameConfig,
+};
+
+const LAYOUT_DEBUG_TOGGLE_KEY: KeyCode = KeyCode::F2;
+const LAYOUT_DEBUG_STEP_KEY: KeyCode = KeyCode::N;
+const LAYOUT_DEBUG_RUN_KEY: KeyCode = KeyCode::ShiftLeft;
+const LAYOUT_DEBUG_RESEED_KEY: KeyCode = KeyCode::R;
+const CANDIDATE_ALPHA: f32 = 0.5;
+
+// Gameplay systems pause while this is open
+#[derive(Resource)]
+pub struct LayoutDebugger {
+    seed: u64,
+    packer: BrickPacker,
+    last_step: Option<PackerStep>,
+    step_number: usize,
+}
+
+impl LayoutDebugger {
+    fn new(seed: u64, game_config: &GameConfig) -> Self {
+        LayoutDebugger {
+            seed,
+            packer: BrickPacker::new(game_config.get_brick_bounding_box(), game_config, seed),
+            last_step: None,
+            step_number: 0,
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct LayoutDebugText;
+
+/// Steps the brick packer one square at a time over the running game.
+pub struct LayoutDebugPlugin;
+
+impl Plugin for LayoutDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            toggle_layout_debugger.run_if(in_state(AppState::InGame)),
+        )
+        .add_systems(
+            Update,
+            (
+                step_layout_debugger,
+                draw_layout_debugger,
+                update_layout_debug_text,
+            )
+                .chain()
+                .run_if(resource_exists::<LayoutDebugger>()),
+        )
+        .add_systems(OnExit(AppState::InGame), close_layout_debugger);
+    }
+}
+
+fn toggle_layout_debugger(
+    mut commands: Commands,
+    layout_debugger: Option<Res<LayoutDebugger>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut brick_query: Query<&mut Visibility, With<Brick>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    text_query: Query<Entity, With<LayoutDebugText>>,
+    layout_seed: Option<Res<LayoutSeed>>,
+    theme: Res<Theme>,
+    game_config: Res<GameConfig>,
+) {
+    if !keyboard_input.just_pressed(LAYOUT_DEBUG_TOGGLE_KEY) {
+        return;
+    }
+
+    if layout_debugger.is_some() {
+        commands.remove_resource::<LayoutDebugger>();
+        for entity in text_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        for mut visibility in brick_query.iter_mut() {
+            *visibility = Visibility::Inherited;
+        }
+        rapier_config.physics_pipeline_active = true;
+        return;
+    }
+
+    // Fields loaded from level files have no layout to replay
+    let Some(layout_seed) = layout_seed else {
+        return;
+    };
+    commands.insert_resource(LayoutDebugger::new(layout_seed.seed, &game_config));
+    for mut visibility in brick_query.iter_mut() {
+        *visibility = Visibility::Hidden;
+    }
+    rapier_config.physics_pipeline_active = false;
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.0,
+                color: theme.text,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(5.0),
+            bottom: Val::Px(5.0),
+            ..default()
+        }),
+        LayoutDebugText,
+    ));
+}
+
+fn close_layout_debugger(
+    mut commands: Commands,
+    text_query: Query<Entity, With<LayoutDebugText>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    commands.remove_resource::<LayoutDebugger>();
+    for entity in text_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    rapier_config.physics_pipeline_active = true;
+}
+
+fn step_layout_debugger(
+    mut layout_debugger: ResMut<LayoutDebugger>,
+    keyboard_input: Res<Input<KeyCode>>,
+    game_config: Res<GameConfig>,
+) {
+    if keyboard_input.just_pressed(LAYOUT_DEBUG_RESEED_KEY) {
+        let seed = thread_rng().gen_range(u64::MIN..=u64::MAX);
+        info!("layout debugger seed: {seed}");
+        *layout_debugger = LayoutDebugger::new(seed, &game_config);
+        return;
+    }
+
+    // Holding the run key keeps stepping every frame
+    let should_step = keyboard_input.just_pressed(LAYOUT_DEBUG_STEP_KEY)
+        || (keyboard_input.pressed(LAYOUT_DEBUG_STEP_KEY)
+            && keyboard_input.pressed(LAYOUT_DEBUG_RUN_KEY));
+    if !should_step || layout_debugger.packer.is_finished() {
+        return;
+    }
+
+    let step = layout_debugger.packer.step();
+    if step.is_some() {
+        layout_debugger.step_number += 1;
+    }
+    layout_debugger.last_step = step;
+}
+
+fn edge_line(edge: &Edge) -> (Vec2, Vec2) {
+    match edge.axis {
+        Axis::X => (
+            Vec2::new(edge.start, edge.pos),
+            Vec2::new(edge.end, edge.pos),
+        ),
+        Axis::Y => (
+            Vec2::new(edge.pos, edge.start),
+            Vec2::new(edge.pos, edge.end),
+        ),
+    }
+}
+
+fn draw_layout_debugger(
+    mut gizmos: Gizmos,
+    layout_debugger: Res<LayoutDebugger>,
+    theme: Res<Theme>,
+    game_config: Res<GameConfig>,
+) {
+    let bounding_box = game_config.get_brick_bounding_box();
+    gizmos.rect_2d(
+        bounding_box.translation.truncate(),
+        0.0,
+        bounding_box.scale.truncate(),
+        Color::GRAY,
+    );
+
+    for square in layout_debugger.packer.squares() {
+        gizmos.rect_2d(
+            square.translation.truncate(),
+            0.0,
+            square.scale.truncate() - game_config.brick_margin,
+            get_brick_color(square.scale.x, &theme.bricks, &game_config),
+        );
+    }
+
+    let Some(step) = &layout_debugger.last_step else {
+        return;
+    };
+
+    for edge in step
+        .trunc_horizontal_edges
+        .iter()
+        .chain(step.trunc_vertical_edges.iter())
+    {
+        let (start, end) = edge_line(edge);
+        gizmos.line_2d(start, end, get_edge_debug_color(edge, 1.0));
+    }
+
+    // Candidate positions are the lines the square center can be placed on
+    for edge in step.positions.iter() {
+        let (start, end) = edge_line(edge);
+        gizmos.line_2d(start, end, get_edge_debug_color(edge, CANDIDATE_ALPHA));
+        gizmos.circle_2d(start, 2.0, get_edge_debug_color(edge, CANDIDATE_ALPHA));
+        gizmos.circle_2d(end, 2.0, get_edge_debug_color(edge, CANDIDATE_ALPHA));
+    }
+
+    let (start, end) = edge_line(&step.chosen_edge);
+    gizmos.line_2d(start, end, theme.text);
+    gizmos.rect_2d(
+        step.square.translation.truncate(),
+        0.0,
+        step.square.scale.truncate(),
+        theme.text,
+    );
+}
+
+fn update_layout_debug_text(
+    layout_debugger: Res<LayoutDebugger>,
+    mut text_query: Query<&mut Text, With<LayoutDebugText>>,
+) {
+    let status = match (
+        &layout_debugger.last_step,
+        layout_debugger.packer.is_finished(),
+    ) {
+        (_, true) => format!(
+            "finished with {} bricks",
+            layout_debugger.packer.squares().len()
+        ),
+        (Some(step), false) => format!(
+            "sampled size: {}, candidates: {}, chosen edge: {:?} {:?} at {:.1} from {:.1} to {:.1}",
+            step.square_side_length,
+            step.positions.len(),
+            step.chosen_edge.axis,
+            step.chosen_edge.side,
+            step.chosen_edge.pos,
+            step.chosen_edge.start,
+            step.chosen_edge.end,
+        ),
+        (None, false) => "first brick placed".to_string(),
+    };
+
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = format!(
+            "Layout debugger - seed: {}, step: {}\n{}\n\
+             [N] step  [Shift+N] run  [R] new seed  [F2] close",
+            layout_debugger.seed, layout_debugger.step_number, status,
+        );
+    }
+}
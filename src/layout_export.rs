@@ -0,0 +1,267 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use std::{fmt::Write, fs, path::Path, process};
+
+use crate::{
+    bricks::{
+        compute_brick_layout, get_brick_color, get_brick_color_index, get_edge_debug_color, Axis,
+        BrickLayout, Edge,
+    },
+    theme::{Theme, ThemeSelection},
+    GameConfig,
+};
+
+const USAGE: &str = "Usage: breakout layout <config.yaml> [--seed <u64>] [--limit <step>] \
+                     [--svg <file.svg>] [--ascii] [--cell <size>]";
+// Same order as the brick colors of the built in theme
+const ASCII_BRICK_CHARS: [char; 7] = ['R', 'O', 'Y', 'G', 'B', 'I', 'V'];
+const ASCII_EMPTY_CHAR: char = '.';
+
+struct LayoutExportArgs {
+    config_path: String,
+    seed: Option<u64>,
+    limit: Option<i32>,
+    svg_path: Option<String>,
+    ascii: bool,
+    cell_size: Option<f32>,
+}
+
+pub fn run(args: &[String]) {
+    let export_args = match parse_args(args) {
+        Ok(export_args) => export_args,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            process::exit(2);
+        }
+    };
+
+    let game_config = match GameConfig::parse(&export_args.config_path) {
+        Ok(game_config) => game_config,
+        Err(e) => {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+    };
+
+    let seed = export_args
+        .seed
+        .unwrap_or_else(|| thread_rng().gen_range(u64::MIN..=u64::MAX));
+    let layout = compute_brick_layout(
+        game_config.get_brick_bounding_box(),
+        &game_config,
+        seed,
+        export_args.limit,
+    );
+    println!("seed: {seed}");
+    println!("bricks: {}", layout.squares.len());
+
+    if let Some(svg_path) = &export_args.svg_path {
+        let theme = Theme::select(&ThemeSelection(None), Some(&game_config));
+        if let Err(e) = fs::write(svg_path, layout_to_svg(&layout, &theme, &game_config)) {
+            eprintln!("Failed to write {svg_path}: {e}");
+            process::exit(1);
+        }
+        println!("wrote {svg_path}");
+    }
+
+    if export_args.ascii || export_args.svg_path.is_none() {
+        let cell_size = export_args
+            .cell_size
+            .unwrap_or(game_config.brick_min_width / 2.0);
+        print!("{}", layout_to_ascii(&layout, &game_config, cell_size));
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<LayoutExportArgs, String> {
+    let mut config_path = None;
+    let mut seed = None;
+    let mut limit = None;
+    let mut svg_path = None;
+    let mut ascii = false;
+    let mut cell_size = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {name}"))
+        };
+        match arg.as_str() {
+            "--seed" => {
+                seed = Some(
+                    value("--seed")?
+                        .parse()
+                        .map_err(|e| format!("Invalid seed: {e}"))?,
+                )
+            }
+            "--limit" => {
+                limit = Some(
+                    value("--limit")?
+                        .parse()
+                        .map_err(|e| format!("Invalid limit: {e}"))?,
+                )
+            }
+            "--svg" => svg_path = Some(value("--svg")?),
+            "--ascii" => ascii = true,
+            "--cell" => {
+                cell_size = Some(
+                    value("--cell")?
+                        .parse::<f32>()
+                        .map_err(|e| format!("Invalid cell size: {e}"))?
+                        .max(1.0),
+                )
+            }
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {arg}")),
+            _ if config_path.is_none() => config_path = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument {arg}")),
+        }
+    }
+
+    let config_path = config_path.ok_or("Missing config file")?;
+    // Bare file names are looked up in the assets directory like in the config menu
+    let config_path = if Path::new(&config_path).exists() {
+        config_path
+    } else {
+        format!("./assets/{config_path}")
+    };
+
+    Ok(LayoutExportArgs {
+        config_path,
+        seed,
+        limit,
+        svg_path,
+        ascii,
+        cell_size,
+    })
+}
+
+fn svg_color(color: Color) -> String {
+    let [r, g, b, a] = color.as_rgba_u8();
+    format!(
+        "fill=\"#{r:02x}{g:02x}{b:02x}\" fill-opacity=\"{:.3}\"",
+        a as f32 / 255.0
+    )
+}
+
+fn layout_to_svg(layout: &BrickLayout, theme: &Theme, game_config: &GameConfig) -> String {
+    let width = game_config.area_width + 2.0 * game_config.wall_thickness;
+    let height = game_config.area_height + 2.0 * game_config.wall_thickness;
+    // SVG y axis points down and its origin is the top left corner
+    let left = -width / 2.0;
+    let top = height / 2.0;
+
+    let mut svg = String::new();
+    let rect = |svg: &mut String, center: Vec2, size: Vec2, color: Color| {
+        let _ = writeln!(
+            svg,
+            "  <rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" {}/>",
+            center.x - size.x / 2.0 - left,
+            top - center.y - size.y / 2.0,
+            size.x,
+            size.y,
+            svg_color(color),
+        );
+    };
+
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\">"
+    );
+    rect(&mut svg, Vec2::ZERO, Vec2::new(width, height), Color::GRAY);
+    rect(
+        &mut svg,
+        Vec2::ZERO,
+        Vec2::new(game_config.area_width, game_config.area_height),
+        theme.background,
+    );
+
+    for square in layout.squares.iter() {
+        let side_length = square.scale.x;
+        let outer = Vec2::new(
+            side_length - game_config.brick_margin,
+            side_length - game_config.brick_margin,
+        );
+        let inner = outer * (side_length - theme.brick_border_width) / side_length;
+        let center = square.translation.truncate();
+        rect(
+            &mut svg,
+            center,
+            outer,
+            get_brick_color(side_length, &theme.brick_borders, game_config),
+        );
+        rect(
+            &mut svg,
+            center,
+            inner,
+            get_brick_color(side_length, &theme.bricks, game_config),
+        );
+    }
+
+    if let Some(debug_step) = &layout.debug_step {
+        let edges = |svg: &mut String, edges: &Vec<Edge>, side_length: f32, alpha: f32| {
+            for edge in edges {
+                let (center, size) = match edge.axis {
+                    Axis::X => (
+                        Vec2::new((edge.start + edge.end) / 2.0, edge.pos),
+                        Vec2::new(edge.end - edge.start + side_length, side_length),
+                    ),
+                    Axis::Y => (
+                        Vec2::new(edge.pos, (edge.start + edge.end) / 2.0),
+                        Vec2::new(side_length, edge.end - edge.start + side_length),
+                    ),
+                };
+                rect(svg, center, size, get_edge_debug_color(edge, alpha));
+            }
+        };
+        edges(
+            &mut svg,
+            &debug_step.positions,
+            debug_step.square_side_length,
+            0.08,
+        );
+        edges(&mut svg, &debug_step.positions, 1.0, 1.0);
+        edges(&mut svg, &debug_step.horizontal_edges, 1.0, 1.0);
+        edges(&mut svg, &debug_step.vertical_edges, 1.0, 1.0);
+        edges(&mut svg, &debug_step.trunc_horizontal_edges, 4.0, 1.0);
+        edges(&mut svg, &debug_step.trunc_vertical_edges, 4.0, 1.0);
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn layout_to_ascii(layout: &BrickLayout, game_config: &GameConfig, cell_size: f32) -> String {
+    let bounding_box = game_config.get_brick_bounding_box();
+    let columns = (bounding_box.scale.x / cell_size).ceil() as usize;
+    let rows = (bounding_box.scale.y / cell_size).ceil() as usize;
+    let left = bounding_box.translation.x - bounding_box.scale.x / 2.0;
+    let top = bounding_box.translation.y + bounding_box.scale.y / 2.0;
+
+    let mut ascii = String::with_capacity((columns + 1) * rows);
+    for row in 0..rows {
+        for column in 0..columns {
+            let cell_center = Vec2::new(
+                left + (column as f32 + 0.5) * cell_size,
+                top - (row as f32 + 0.5) * cell_size,
+            );
+            let brick = layout.squares.iter().find(|square| {
+                let half_size = (square.scale.x - game_config.brick_margin) / 2.0;
+                (cell_center - square.translation.truncate())
+                    .abs()
+                    .cmple(Vec2::splat(half_size))
+                    .all()
+            });
+            ascii.push(match brick {
+                Some(square) => {
+                    ASCII_BRICK_CHARS[get_brick_color_index(square.scale.x, game_config)
+                        % ASCII_BRICK_CHARS.len()]
+                }
+                None => ASCII_EMPTY_CHAR,
+            });
+        }
+        ascii.push('\n');
+    }
+    ascii
+}
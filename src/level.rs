@@ -0,0 +1,55 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::{bricks::get_brick_color_index, GameConfig};
+
+pub const LEVELS_DIRECTORY: &str = "./assets/levels";
+
+/// A hand placed brick. `brick_type` picks the color and score band the same
+/// way the brick width does for generated bricks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LevelBrick {
+    pub x: f32,
+    pub y: f32,
+    pub size: f32,
+    pub brick_type: usize,
+}
+
+impl LevelBrick {
+    pub fn from_square(square: &Transform, game_config: &GameConfig) -> Self {
+        LevelBrick {
+            x: square.translation.x,
+            y: square.translation.y,
+            size: square.scale.x,
+            brick_type: get_brick_color_index(square.scale.x, game_config),
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Level {
+    pub bricks: Vec<LevelBrick>,
+}
+
+impl Level {
+    pub fn load(file_path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(file_path)
+            .map_err(|e| format!("Failed to read {file_path}: {e}"))?;
+
+        serde_yaml::from_str(&contents).map_err(|e| format!("Failed to parse {file_path}: {e}"))
+    }
+
+    pub fn save(&self, file_path: &str) -> Result<(), String> {
+        let contents =
+            serde_yaml::to_string(self).map_err(|e| format!("Failed to serialize level: {e}"))?;
+        fs::create_dir_all(LEVELS_DIRECTORY)
+            .map_err(|e| format!("Failed to create {LEVELS_DIRECTORY}: {e}"))?;
+
+        fs::write(file_path, contents).map_err(|e| format!("Failed to write {file_path}: {e}"))
+    }
+}
+
+/// Level being edited, kept around so test games and the editor share it.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct EditorLevel(pub Level);
@@ -1,9 +1,24 @@
+mod accessibility;
+mod achievements;
+mod audio;
 mod ball;
 mod bricks;
 mod camera;
 mod debug;
+mod editor;
+mod effects;
+mod events;
+mod history;
+mod layout_debug;
+mod layout_export;
+mod level;
 mod paddle;
+mod profile;
+mod save;
 mod score;
+mod settings;
+mod stats;
+mod theme;
 mod walls;
 
 use bevy::{
@@ -17,16 +32,29 @@ use notify_rust::Notification;
 use serde::Deserialize;
 use std::f32::consts::PI;
 use std::fs;
+use std::path::Path;
 
+use accessibility::AccessibilityPlugin;
+use achievements::AchievementsPlugin;
+use audio::GameAudioPlugin;
 use ball::BallPlugin;
 use bricks::BrickPlugin;
 use camera::CameraPlugin;
+use debug::DebugHudPlugin;
+use editor::EditorPlugin;
+use effects::EffectsPlugin;
+use events::GameplayEventsPlugin;
+use history::HistoryPlugin;
+use layout_debug::LayoutDebugPlugin;
 use paddle::PaddlePlugin;
+use profile::ProfilePlugin;
+use save::{ContinueRound, SavePlugin, SavedRound};
 use score::ScorePlugin;
+use stats::StatsPlugin;
+use theme::{Theme, ThemePlugin};
 use walls::WallPlugin;
 
 const DEGREE_TO_RADIAN_FACTOR: f32 = PI / 180.0;
-const BACKGROUND_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
 const SCREEN_WIDTH: i32 = 900;
 const SCREEN_HEIGHT: i32 = 800;
 // const PHYSICS_UPDATES_PER_SECOND: i32 = 60;
@@ -38,8 +66,86 @@ enum AppState {
     SelectConfig,
     InGame,
     GaveOver,
+    Editor,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum BallSpeedCurve {
+    #[default]
+    Linear,
+    Logarithmic,
+    Stepped,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ScoreDecay {
+    None,
+    #[default]
+    Constant,
+    Accelerating,
+    Countdown,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum StatsExport {
+    #[default]
+    None,
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ArenaShape {
+    #[default]
+    Box,
+    Circle,
+    Polygon,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum WallBehavior {
+    #[default]
+    Solid,
+    Death,
+    // Sends the ball out of the opposite wall
+    Wrap,
+    Bouncy,
+    Boost,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum PaddleSide {
+    #[default]
+    Bottom,
+    Top,
+}
+
+/// Gap in the top wall, `x` is its middle.
+#[derive(Debug, Clone, Deserialize)]
+struct ArenaOpening {
+    x: f32,
+    width: f32,
+}
+
+/// Fixed rectangle inside the arena, centered on `x`, `y` and turned by `angle` degrees.
+#[derive(Debug, Clone, Deserialize)]
+struct ArenaObstacle {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    #[serde(default)]
+    angle: f32,
+}
+
+// Fields added since the first configs default to the behavior from before they existed, so
+// older configs still load
 #[derive(Resource, Deserialize, TypePath)]
 struct GameConfig {
     window_width: i32,
@@ -49,18 +155,71 @@ struct GameConfig {
     ball_initial_speed: f32,
     ball_speed: f32,
     score_to_ball_speed_factor: f32,
+    #[serde(default)]
+    ball_speed_curve: BallSpeedCurve,
+    #[serde(default)]
+    ball_speed_step_bricks: i32,
+    #[serde(default)]
+    ball_speed_step: f32,
+    #[serde(default = "default_ball_max_speed")]
+    ball_max_speed: f32,
+    #[serde(default)]
+    ball_speed_decay: f32,
     ball_anti_gravity_time: f32,
     ball_gravity_scale: f32,
     ball_restitution: f32,
+    #[serde(default)]
+    ball_min_trajectory_angle: f32,
+    #[serde(default)]
+    ball_stuck_time: f32,
+    #[serde(default)]
+    ball_stuck_bounces: i32,
+    #[serde(default)]
+    ball_nudge_angle: f32,
+    #[serde(default)]
+    ball_serve_on_paddle: bool,
+    #[serde(default)]
+    ball_serve_timeout: f32,
+    #[serde(default)]
+    ball_serve_max_angle: f32,
     wall_thickness: f32,
     area_width: f32,
     area_height: f32,
+    // Circles and polygons with `arena_sides` sides are stretched to fill the area
+    #[serde(default)]
+    arena_shape: ArenaShape,
+    #[serde(default)]
+    arena_sides: i32,
+    // Length of the angled bumpers in the top corners of the box along its walls, 0 for none
+    #[serde(default)]
+    arena_corner_bumper: f32,
+    // Gaps in the wall of the box across from the paddle, the ball is lost through them
+    #[serde(default)]
+    arena_top_openings: Vec<ArenaOpening>,
+    #[serde(default)]
+    arena_obstacles: Vec<ArenaObstacle>,
+    // Sides of circles and polygons behave like the wall they face
+    #[serde(default)]
+    wall_left: WallBehavior,
+    #[serde(default)]
+    wall_right: WallBehavior,
+    // Unset, the wall behind the paddle loses the ball and the one across from it is solid
+    wall_bottom: Option<WallBehavior>,
+    wall_top: Option<WallBehavior>,
+    // Restitution of bouncy walls, above 1 speeds the ball up until it hits the paddle
+    #[serde(default)]
+    wall_bounce_restitution: f32,
+    // Speed given to the ball by boost walls, lost again with `ball_speed_decay`
+    #[serde(default)]
+    wall_boost_speed: f32,
     brick_min_width: f32,
     brick_max_width: f32,
     brick_width_step: f32,
     brick_margin: f32,
     brick_bottom_margin_ratio: f32,
     brick_top_margin_ratio: f32,
+    #[serde(default)]
+    paddle_side: PaddleSide,
     paddle_bottom_margin: f32,
     paddle_width: f32,
     paddle_height: f32,
@@ -68,40 +227,173 @@ struct GameConfig {
     paddle_collider_segments: i32,
     paddle_max_speed: f32,
     paddle_acceleration: f32,
+    #[serde(default)]
+    paddle_aim_control: bool,
+    #[serde(default)]
+    paddle_aim_min_angle: f32,
+    #[serde(default)]
+    paddle_aim_max_angle: f32,
+    #[serde(default)]
+    paddle_aim_velocity_factor: f32,
     brick_max_score: i32,
     brick_min_score: i32,
     score_loss_interval: f32,
     score_loss: i32,
+    #[serde(default)]
+    score_decay: ScoreDecay,
+    #[serde(default)]
+    score_loss_acceleration: f32,
+    #[serde(default)]
+    score_time_bonus: i32,
+    score_floor: Option<i32>,
     win_score_bonus: i32,
+    #[serde(default)]
+    combo_multiplier_step: f32,
+    #[serde(default)]
+    combo_max_multiplier: f32,
+    #[serde(default)]
+    region_columns: i32,
+    #[serde(default)]
+    region_rows: i32,
+    #[serde(default)]
+    region_clear_bonus: i32,
+    // Level file in the assets directory, bricks are generated when absent
+    level: Option<String>,
+    // Looping music file in the assets directory
+    music: Option<String>,
+    // Theme file in assets/themes, a theme picked on the config menu takes precedence
+    theme: Option<String>,
+    // Images in the assets directory for the ball, paddle and bricks, over those of the theme
+    ball_image: Option<String>,
+    paddle_image: Option<String>,
+    brick_image: Option<String>,
+    // Brick shards, impact squash and ball trail, off for low end machines
+    #[serde(default)]
+    effects: bool,
+    // Writes the events of every round to a session file in the data directory
+    #[serde(default)]
+    stats_export: StatsExport,
+    #[serde(skip)]
+    file_name: String,
+}
+fn default_ball_max_speed() -> f32 {
+    f32::INFINITY
 }
+
 impl GameConfig {
     fn load(file_path: &str) -> Self {
-        let contents =
-            std::fs::read_to_string(file_path).expect(&format!("Failed to read {}", file_path));
-
-        match serde_yaml::from_str(&contents) {
+        match Self::parse(file_path) {
             Ok(game_config) => game_config,
             Err(e) => {
-                show_notification(
-                    "Config File Parsing Error",
-                    &format!("Failed to parse {file_path}: {e}"),
-                );
-                panic!("Failed to parse {file_path}: {e}");
+                show_notification("Config File Parsing Error", &e);
+                panic!("{e}");
             }
         }
     }
 
+    fn parse(file_path: &str) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(file_path)
+            .map_err(|e| format!("Failed to read {file_path}: {e}"))?;
+
+        let mut game_config: GameConfig = serde_yaml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {file_path}: {e}"))?;
+        game_config.file_name = Path::new(file_path)
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(game_config)
+    }
+
+    // 1.0 when the paddle is at the bottom and -1.0 when it is at the top, everything measured
+    // from the paddle side of the arena is mirrored with it
+    fn get_paddle_side_sign(&self) -> f32 {
+        match self.paddle_side {
+            PaddleSide::Bottom => 1.0,
+            PaddleSide::Top => -1.0,
+        }
+    }
+
+    fn get_wall_bottom(&self) -> WallBehavior {
+        self.wall_bottom.unwrap_or(match self.paddle_side {
+            PaddleSide::Bottom => WallBehavior::Death,
+            PaddleSide::Top => WallBehavior::Solid,
+        })
+    }
+
+    fn get_wall_top(&self) -> WallBehavior {
+        self.wall_top.unwrap_or(match self.paddle_side {
+            PaddleSide::Bottom => WallBehavior::Solid,
+            PaddleSide::Top => WallBehavior::Death,
+        })
+    }
+
     fn get_ball_starting_position(&self) -> Vec3 {
         let distance_to_bottom =
             self.brick_bottom_margin_ratio * self.area_height - self.ball_diameter / 2.0;
         let y = -self.area_height / 2.0 + distance_to_bottom;
         let x = -((self.area_width / 2.0)
             .min(distance_to_bottom - self.paddle_bottom_margin - self.paddle_height));
-        Vec3::new(x, y, 1.0)
+        Vec3::new(x, y * self.get_paddle_side_sign(), 1.0)
     }
 
     fn get_ball_initial_linvel(&self) -> Vec2 {
-        Vec2::from_angle(-45.0 * DEGREE_TO_RADIAN_FACTOR) * self.ball_initial_speed
+        Vec2::from_angle(-45.0 * DEGREE_TO_RADIAN_FACTOR)
+            * Vec2::new(1.0, self.get_paddle_side_sign())
+            * self.ball_initial_speed
+    }
+
+    // Speed added to `ball_speed` after scoring `total_score` over `bricks_destroyed` bricks
+    fn get_ball_speed_bonus(&self, total_score: i32, bricks_destroyed: i32) -> f32 {
+        match self.ball_speed_curve {
+            BallSpeedCurve::Linear => total_score as f32 * self.score_to_ball_speed_factor,
+            // Same slope as linear at first, flattening out as the score grows
+            BallSpeedCurve::Logarithmic => {
+                let scale = self.brick_max_score.max(1) as f32;
+                self.score_to_ball_speed_factor * scale * (total_score as f32 / scale).ln_1p()
+            }
+            BallSpeedCurve::Stepped => {
+                (bricks_destroyed / self.ball_speed_step_bricks.max(1)) as f32
+                    * self.ball_speed_step
+            }
+        }
+    }
+
+    fn get_ball_serve_direction(&self, paddle_velocity: f32) -> Vec2 {
+        let angle = (paddle_velocity / self.paddle_max_speed).clamp(-1.0, 1.0)
+            * self.ball_serve_max_angle
+            * DEGREE_TO_RADIAN_FACTOR;
+        Vec2::new(angle.sin(), angle.cos() * self.get_paddle_side_sign())
+    }
+
+    // Angles are measured from the vertical pointing away from the paddle, the offset goes
+    // from -1.0 on the left end of the paddle to 1.0 on the right end
+    fn get_paddle_bounce_direction(&self, offset: f32, paddle_velocity: f32) -> Vec2 {
+        let aim = (offset
+            + paddle_velocity / self.paddle_max_speed * self.paddle_aim_velocity_factor)
+            .clamp(-1.0, 1.0);
+        // A dead centre hit without paddle motion has no side to favour
+        let side = if aim == 0.0 {
+            if rand::random::<bool>() {
+                1.0
+            } else {
+                -1.0
+            }
+        } else {
+            aim.signum()
+        };
+        let angle = side
+            * (self.paddle_aim_min_angle
+                + aim.abs() * (self.paddle_aim_max_angle - self.paddle_aim_min_angle))
+            * DEGREE_TO_RADIAN_FACTOR;
+        Vec2::new(angle.sin(), angle.cos() * self.get_paddle_side_sign())
+    }
+
+    // Play area plus the walls around it, kept in view whatever the window size
+    fn get_arena_size(&self) -> Vec2 {
+        Vec2::new(
+            self.area_width + 2.0 * self.wall_thickness,
+            self.area_height + 2.0 * self.wall_thickness,
+        )
     }
 
     fn get_brick_bounding_box(&self) -> Transform {
@@ -110,7 +402,7 @@ impl GameConfig {
             - self.area_height / 2.0;
 
         Transform {
-            translation: Vec3::new(0.0, mid_y, 0.0),
+            translation: Vec3::new(0.0, mid_y * self.get_paddle_side_sign(), 0.0),
             scale: Vec3::new(self.area_width, height_ratio * self.area_height, 1.0),
             ..default()
         }
@@ -124,6 +416,70 @@ impl GameConfig {
             .round() as i32
     }
 
+    // Points lost on the `interval`th tick of the score loss timer, counting from 0
+    fn get_score_loss(&self, interval: i32) -> i32 {
+        match self.score_decay {
+            ScoreDecay::Constant => self.score_loss,
+            ScoreDecay::Accelerating => (self.score_loss as f32
+                * (1.0 + self.score_loss_acceleration * interval as f32))
+                .round() as i32,
+            ScoreDecay::None | ScoreDecay::Countdown => 0,
+        }
+    }
+
+    // The floor only stops the decay, it never raises a score already below it
+    fn get_decayed_score(&self, score: i32, loss: i32) -> i32 {
+        let floor = self
+            .score_floor
+            .map_or(i32::MIN, |score_floor| score_floor.min(score));
+        (score - loss).max(floor)
+    }
+
+    fn describe_score_decay(&self) -> String {
+        let decay = match self.score_decay {
+            ScoreDecay::None => "no score decay".to_string(),
+            ScoreDecay::Constant => format!(
+                "constant decay of {} every {}s",
+                self.score_loss, self.score_loss_interval
+            ),
+            ScoreDecay::Accelerating => format!(
+                "accelerating decay from {} every {}s, +{}% per tick",
+                self.score_loss,
+                self.score_loss_interval,
+                self.score_loss_acceleration * 100.0
+            ),
+            ScoreDecay::Countdown => format!(
+                "time bonus of {} losing {} every {}s",
+                self.score_time_bonus, self.score_loss, self.score_loss_interval
+            ),
+        };
+        match self.score_floor {
+            Some(score_floor) => format!("{decay}, floor {score_floor}"),
+            None => decay,
+        }
+    }
+
+    // Multiplier for the `streak`th brick destroyed since the ball last touched the paddle
+    fn get_combo_multiplier(&self, streak: i32) -> f32 {
+        (1.0 + self.combo_multiplier_step * (streak - 1).max(0) as f32)
+            .min(self.combo_max_multiplier.max(1.0))
+    }
+
+    // Index of the grid cell of the brick bounding box containing `position`
+    fn get_brick_region(&self, position: Vec2) -> Option<i32> {
+        if self.region_columns <= 0 || self.region_rows <= 0 {
+            return None;
+        }
+        let bounding_box = self.get_brick_bounding_box();
+        let relative = (position - bounding_box.translation.truncate())
+            / bounding_box.scale.truncate()
+            + 0.5;
+        let column =
+            ((relative.x * self.region_columns as f32) as i32).clamp(0, self.region_columns - 1);
+        let row = ((relative.y * self.region_rows as f32) as i32).clamp(0, self.region_rows - 1);
+        Some(row * self.region_columns + column)
+    }
+
     fn get_paddle_segment_point(&self, i: i32, segments: i32) -> Vec2 {
         let radius =
             self.paddle_height / 2.0 + self.paddle_width.powi(2) / (8.0 * self.paddle_height);
@@ -140,9 +496,15 @@ impl GameConfig {
 struct ConfigFilesUI;
 
 #[derive(Component)]
-struct ConfigFileOption(String);
+struct ConfigFileOption(String, AppState);
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("layout") {
+        layout_export::run(&args[1..]);
+        return;
+    }
+
     App::new()
         // Bevy plugins
         .add_plugins(DefaultPlugins.set(WindowPlugin {
@@ -156,7 +518,6 @@ fn main() {
             ..default()
         }))
         // .insert_resource(Msaa::Off)
-        .insert_resource(ClearColor(BACKGROUND_COLOR))
         // .add_plugins(FrameTimeDiagnosticsPlugin::default())
         // .add_plugins(LogDiagnosticsPlugin::default())
         // .insert_resource(RapierConfiguration {
@@ -181,24 +542,51 @@ fn main() {
         // .add_plugins(YamlAssetPlugin::<GameConfig>::new(&["config.yaml"]))
         // User
         .add_state::<AppState>()
+        .add_plugins(AccessibilityPlugin)
+        .add_plugins(ThemePlugin)
         .add_plugins(CameraPlugin)
+        .add_plugins(GameplayEventsPlugin)
         .add_plugins(BallPlugin)
         .add_plugins(WallPlugin)
         .add_plugins(BrickPlugin)
         .add_plugins(PaddlePlugin)
         .add_plugins(ScorePlugin)
+        .add_plugins(StatsPlugin)
+        .add_plugins(HistoryPlugin)
+        .add_plugins(SavePlugin)
+        .add_plugins(ProfilePlugin)
+        .add_plugins(AchievementsPlugin)
+        .add_plugins(GameAudioPlugin)
+        .add_plugins(EffectsPlugin)
+        .add_plugins(LayoutDebugPlugin)
+        .add_plugins(EditorPlugin)
+        .add_plugins(DebugHudPlugin)
         .add_systems(OnEnter(AppState::SelectConfig), spawn_game_config_ui)
         .add_systems(
             Update,
-            handle_config_click.run_if(in_state(AppState::SelectConfig)),
+            (
+                handle_config_click,
+                (despawn_game_config_ui, spawn_game_config_ui).run_if(resource_changed::<Theme>()),
+            )
+                .chain()
+                .run_if(in_state(AppState::SelectConfig)),
+        )
+        .add_systems(
+            OnExit(AppState::SelectConfig),
+            (despawn_game_config_ui, exit_select_config),
         )
-        .add_systems(OnExit(AppState::SelectConfig), exit_select_config)
         .add_systems(Update, leave_game)
         // .add_plugins(DebugPlugin)
         .run();
 }
 
-fn spawn_game_config_ui(mut commands: Commands) {
+fn spawn_game_config_ui(
+    mut commands: Commands,
+    mut window_query: Query<&mut Window>,
+    theme: Res<Theme>,
+) {
+    window_query.single_mut().cursor.visible = true;
+
     let asset_entries = match fs::read_dir("./assets") {
         Ok(entries) => entries,
         Err(e) => {
@@ -247,7 +635,7 @@ fn spawn_game_config_ui(mut commands: Commands) {
                     "Select game config file",
                     TextStyle {
                         font_size: 36.0,
-                        color: Color::WHITE,
+                        color: theme.text,
                         ..Default::default()
                     },
                 )
@@ -257,40 +645,87 @@ fn spawn_game_config_ui(mut commands: Commands) {
                 })
                 .with_text_alignment(TextAlignment::Center),
             );
+            // Only offer the saved round while its config still exists
+            let saved_round = SavedRound::load().ok().filter(|saved_round| {
+                Path::new(&format!("./assets/{}", saved_round.config)).exists()
+            });
+            if let Some(saved_round) = saved_round {
+                spawn_config_button(
+                    parent,
+                    &theme,
+                    format!("Continue {}", saved_round.config),
+                    (
+                        ConfigFileOption(saved_round.config, AppState::InGame),
+                        ContinueRound,
+                    ),
+                );
+            }
             for config_file in config_files {
                 parent
-                    .spawn(ButtonBundle {
+                    .spawn(NodeBundle {
                         style: Style {
-                            padding: UiRect::all(Val::Px(10.0)),
-                            margin: UiRect::all(Val::Px(10.0)),
-                            ..Default::default()
+                            display: Display::Flex,
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            ..default()
                         },
-                        background_color: BackgroundColor::from(Color::rgb(0.1, 0.1, 0.1)),
-                        ..Default::default()
+                        ..default()
                     })
-                    .with_children(|button_parent| {
-                        button_parent.spawn(
-                            TextBundle::from_section(
-                                config_file.to_string(),
-                                TextStyle {
-                                    font_size: 24.0,
-                                    color: Color::WHITE,
-                                    ..Default::default()
-                                },
-                            )
-                            .with_style(Style {
-                                margin: UiRect::all(Val::Px(24.0)),
-                                ..Default::default()
-                            })
-                            .with_text_alignment(TextAlignment::Center),
+                    .with_children(|row_parent| {
+                        spawn_config_button(
+                            row_parent,
+                            &theme,
+                            config_file.to_string(),
+                            ConfigFileOption(config_file.clone(), AppState::InGame),
                         );
-                    })
-                    .insert(ConfigFileOption(config_file.clone()));
+                        spawn_config_button(
+                            row_parent,
+                            &theme,
+                            "Edit".to_string(),
+                            ConfigFileOption(config_file.clone(), AppState::Editor),
+                        );
+                    });
             }
         })
         .insert(ConfigFilesUI);
 }
 
+fn spawn_config_button(
+    parent: &mut ChildBuilder,
+    theme: &Theme,
+    label: String,
+    option: impl Bundle,
+) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                padding: UiRect::all(Val::Px(10.0)),
+                margin: UiRect::all(Val::Px(10.0)),
+                ..Default::default()
+            },
+            background_color: BackgroundColor::from(theme.button),
+            ..Default::default()
+        })
+        .with_children(|button_parent| {
+            button_parent.spawn(
+                TextBundle::from_section(
+                    label,
+                    TextStyle {
+                        font_size: 24.0,
+                        color: theme.text,
+                        ..Default::default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(24.0)),
+                    ..Default::default()
+                })
+                .with_text_alignment(TextAlignment::Center),
+            );
+        })
+        .insert(option);
+}
+
 fn handle_config_click(
     mut commands: Commands,
     mut config_query: Query<
@@ -298,39 +733,38 @@ fn handle_config_click(
         Changed<Interaction>,
     >,
     mut next_state: ResMut<NextState<AppState>>,
+    theme: Res<Theme>,
 ) {
     for (mut background_color, config_file, interaction) in config_query.iter_mut() {
         match interaction {
             Interaction::Pressed => {
                 let game_config = GameConfig::load(&format!("./assets/{}", config_file.0));
                 commands.insert_resource(game_config);
-                next_state.set(AppState::InGame);
+                next_state.set(config_file.1);
             }
             Interaction::Hovered => {
-                background_color.0 = Color::rgb(0.4, 0.4, 0.4);
+                background_color.0 = theme.button_hover;
             }
             Interaction::None => {
-                background_color.0 = Color::rgb(0.1, 0.1, 0.1);
+                background_color.0 = theme.button;
             }
         }
     }
 }
 
-fn exit_select_config(
-    mut commands: Commands,
-    ui_query: Query<Entity, With<ConfigFilesUI>>,
-    mut window_query: Query<&mut Window>,
-
-    game_config: Res<GameConfig>,
-) {
+fn despawn_game_config_ui(mut commands: Commands, ui_query: Query<Entity, With<ConfigFilesUI>>) {
     for entity in ui_query.iter() {
         commands.entity(entity).despawn_recursive();
     }
+}
+
+fn exit_select_config(mut window_query: Query<&mut Window>, game_config: Res<GameConfig>) {
     let mut window = window_query.single_mut();
 
     if game_config.window_fullscreen {
         window.mode = WindowMode::BorderlessFullscreen
     } else {
+        window.mode = WindowMode::Windowed;
         window.resolution = WindowResolution::new(
             game_config.window_width as f32,
             game_config.window_height as f32,
@@ -356,3 +790,201 @@ fn show_notification(title: &str, message: &str) {
         .show()
         .expect("Failed to show notification");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_config() -> GameConfig {
+        GameConfig::parse("assets/default.yaml").unwrap()
+    }
+
+    // assets/default.yaml as it was before any of the optional fields existed
+    const FIRST_DEFAULT_CONFIG: &str = r#"
+window_width: 1200
+window_height: 900
+window_fullscreen: false
+
+ball_diameter: 30.0
+ball_initial_speed: 50.0
+ball_speed: 500.0
+score_to_ball_speed_factor: 0.5
+ball_anti_gravity_time: 1.0
+ball_gravity_scale: 2.0
+ball_restitution: 0.75
+
+wall_thickness: 10.0
+area_width: 900.0
+area_height: 700.0
+
+brick_min_width: 30.0
+brick_max_width: 70.0
+brick_width_step: 10.0
+brick_margin: 3.0
+brick_bottom_margin_ratio: 0.3
+brick_top_margin_ratio: 0.20
+
+paddle_bottom_margin: 10.0
+paddle_width: 150.0
+paddle_height: 30.0
+paddle_mesh_segments: 32
+paddle_collider_segments: 5
+paddle_max_speed: 750.0
+paddle_acceleration: 6000.0
+
+brick_max_score: 10
+brick_min_score: 3
+score_loss_interval: 1.0
+score_loss: 1
+win_score_bonus: 500
+"#;
+
+    fn angle_from_vertical(direction: Vec2) -> f32 {
+        direction.x.atan2(direction.y) / DEGREE_TO_RADIAN_FACTOR
+    }
+
+    #[test]
+    fn first_default_config_keeps_its_behavior() {
+        let game_config: GameConfig = serde_yaml::from_str(FIRST_DEFAULT_CONFIG).unwrap();
+        assert_eq!(game_config.ball_speed_curve, BallSpeedCurve::Linear);
+        assert_eq!(game_config.ball_max_speed, f32::INFINITY);
+        assert_eq!(game_config.ball_stuck_bounces, 0);
+        assert!(!game_config.ball_serve_on_paddle);
+        assert_eq!(game_config.arena_shape, ArenaShape::Box);
+        assert_eq!(game_config.wall_left, WallBehavior::Solid);
+        assert_eq!(game_config.wall_right, WallBehavior::Solid);
+        assert_eq!(game_config.get_wall_top(), WallBehavior::Solid);
+        assert_eq!(game_config.get_wall_bottom(), WallBehavior::Death);
+        assert_eq!(game_config.paddle_side, PaddleSide::Bottom);
+        assert!(!game_config.paddle_aim_control);
+        assert_eq!(game_config.score_decay, ScoreDecay::Constant);
+        assert_eq!(game_config.get_combo_multiplier(5), 1.0);
+        assert_eq!(game_config.get_brick_region(Vec2::ZERO), None);
+        assert_eq!(game_config.stats_export, StatsExport::None);
+    }
+
+    #[test]
+    fn centre_hit_leaves_at_min_angle_on_either_side() {
+        let game_config = default_config();
+        let mut sides = Vec::new();
+        for _ in 0..64 {
+            let angle = angle_from_vertical(game_config.get_paddle_bounce_direction(0.0, 0.0));
+            assert!((angle.abs() - game_config.paddle_aim_min_angle).abs() < 1e-3);
+            sides.push(angle > 0.0);
+        }
+        assert!(sides.contains(&true) && sides.contains(&false));
+    }
+
+    #[test]
+    fn edge_hit_leaves_at_max_angle() {
+        let game_config = default_config();
+        let right = angle_from_vertical(game_config.get_paddle_bounce_direction(1.0, 0.0));
+        let left = angle_from_vertical(game_config.get_paddle_bounce_direction(-1.0, 0.0));
+        assert!((right - game_config.paddle_aim_max_angle).abs() < 1e-3);
+        assert!((left + game_config.paddle_aim_max_angle).abs() < 1e-3);
+    }
+
+    #[test]
+    fn paddle_velocity_offsets_the_aim() {
+        let game_config = default_config();
+        let angle = angle_from_vertical(
+            game_config.get_paddle_bounce_direction(0.0, game_config.paddle_max_speed),
+        );
+        let expected = game_config.paddle_aim_min_angle
+            + game_config.paddle_aim_velocity_factor
+                * (game_config.paddle_aim_max_angle - game_config.paddle_aim_min_angle);
+        assert!((angle - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn paddle_at_the_top_sends_the_ball_down() {
+        let bottom_config = default_config();
+        let mut top_config = default_config();
+        top_config.paddle_side = PaddleSide::Top;
+        let bottom = bottom_config.get_paddle_bounce_direction(0.5, 0.0);
+        let top = top_config.get_paddle_bounce_direction(0.5, 0.0);
+        assert!(bottom.y > 0.0);
+        assert!((top - Vec2::new(bottom.x, -bottom.y)).length() < 1e-5);
+        assert!(top_config.get_ball_serve_direction(0.0).y < 0.0);
+        assert!(top_config.get_ball_initial_linvel().y > 0.0);
+    }
+
+    #[test]
+    fn ball_is_lost_behind_the_paddle_at_the_top() {
+        let mut game_config = default_config();
+        game_config.paddle_side = PaddleSide::Top;
+        assert_eq!(game_config.get_wall_top(), WallBehavior::Death);
+        assert_eq!(game_config.get_wall_bottom(), WallBehavior::Solid);
+        game_config.wall_bottom = Some(WallBehavior::Bouncy);
+        assert_eq!(game_config.get_wall_bottom(), WallBehavior::Bouncy);
+    }
+
+    #[test]
+    fn combo_multiplier_grows_per_brick_up_to_the_cap() {
+        let mut game_config = default_config();
+        game_config.combo_multiplier_step = 0.5;
+        game_config.combo_max_multiplier = 2.0;
+        assert_eq!(game_config.get_combo_multiplier(0), 1.0);
+        assert_eq!(game_config.get_combo_multiplier(1), 1.0);
+        assert_eq!(game_config.get_combo_multiplier(2), 1.5);
+        assert_eq!(game_config.get_combo_multiplier(3), 2.0);
+        assert_eq!(game_config.get_combo_multiplier(10), 2.0);
+        // A cap below 1 never reduces the score
+        game_config.combo_max_multiplier = 0.5;
+        assert_eq!(game_config.get_combo_multiplier(3), 1.0);
+    }
+
+    #[test]
+    fn score_loss_follows_the_decay_mode() {
+        let mut game_config = default_config();
+        game_config.score_loss = 2;
+        game_config.score_loss_acceleration = 0.5;
+        for (score_decay, losses) in [
+            (ScoreDecay::None, [0, 0, 0]),
+            (ScoreDecay::Constant, [2, 2, 2]),
+            (ScoreDecay::Accelerating, [2, 3, 4]),
+            (ScoreDecay::Countdown, [0, 0, 0]),
+        ] {
+            game_config.score_decay = score_decay;
+            for (interval, loss) in losses.into_iter().enumerate() {
+                assert_eq!(game_config.get_score_loss(interval as i32), loss);
+            }
+        }
+    }
+
+    #[test]
+    fn score_floor_stops_the_decay() {
+        let mut game_config = default_config();
+        game_config.score_floor = None;
+        assert_eq!(game_config.get_decayed_score(5, 10), -5);
+        game_config.score_floor = Some(0);
+        assert_eq!(game_config.get_decayed_score(15, 10), 5);
+        assert_eq!(game_config.get_decayed_score(5, 10), 0);
+        // Already below the floor, the score stays where it is
+        assert_eq!(game_config.get_decayed_score(-20, 10), -20);
+    }
+
+    #[test]
+    fn ball_speed_bonus_follows_the_curve() {
+        let mut game_config = default_config();
+        game_config.score_to_ball_speed_factor = 0.5;
+        game_config.brick_max_score = 10;
+        game_config.ball_speed_step_bricks = 5;
+        game_config.ball_speed_step = 30.0;
+
+        game_config.ball_speed_curve = BallSpeedCurve::Linear;
+        assert_eq!(game_config.get_ball_speed_bonus(100, 0), 50.0);
+
+        game_config.ball_speed_curve = BallSpeedCurve::Logarithmic;
+        assert_eq!(game_config.get_ball_speed_bonus(0, 0), 0.0);
+        let early = game_config.get_ball_speed_bonus(1, 0);
+        assert!((early - 0.5).abs() < 0.05);
+        let late = game_config.get_ball_speed_bonus(1000, 0);
+        assert!(late > early && late < 100.0);
+
+        game_config.ball_speed_curve = BallSpeedCurve::Stepped;
+        assert_eq!(game_config.get_ball_speed_bonus(1000, 4), 0.0);
+        assert_eq!(game_config.get_ball_speed_bonus(1000, 5), 30.0);
+        assert_eq!(game_config.get_ball_speed_bonus(1000, 12), 60.0);
+    }
+}
@@ -1,13 +1,22 @@
 
-use bevy::{prelude::*, render::render_resource::PrimitiveTopology, sprite::MaterialMesh2dBundle};
+use bevy::{
+    prelude::*,
+    render::render_resource::PrimitiveTopology,
+    sprite::{Anchor, MaterialMesh2dBundle},
+};
 use bevy_rapier2d::prelude::*;
+use std::f32::consts::PI;
 
-use crate::{walls::WallLocation, AppState, GameConfig};
+use crate::{
+    effects::Squash,
+    layout_debug::LayoutDebugger,
+    profile::ActiveProfile,
+    theme::{Skins, Theme},
+    walls::get_arena_half_width,
+    AppState, GameConfig, PaddleSide,
+};
 
-const PADDLE_COLOR: Color = Color::hsl(240.0, 1.0, 0.75);
-const PADDLE_BORDER_COLOR: Color = Color::hsl(240.0, 1.0, 0.1);
 // const PADDLE_BORDER_COLOR: Color = Color::rgb(0.05, 0.05, 0.05);
-const PADDLE_BORDER_WIDTH: f32 = 2.0;
 // pub const PADDLE_STARTING_POSITION: Vec3 = Vec3::new(0.0, -360.0, 1.0);
 // const PADDLE_CHORD_LENGTH: f32 = 150.0;
 // const PADDLE_HEIGHT: f32 = 30.0;
@@ -35,9 +44,13 @@ pub struct PaddlePlugin;
 impl Plugin for PaddlePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(AppState::InGame), spawn_paddle)
+            .add_systems(OnEnter(AppState::Editor), despawn_paddle)
+            .add_systems(OnEnter(AppState::SelectConfig), despawn_paddle)
             .add_systems(
                 Update,
-                paddle_movement_controls.run_if(in_state(AppState::InGame)),
+                paddle_movement_controls.run_if(
+                    in_state(AppState::InGame).and_then(not(resource_exists::<LayoutDebugger>())),
+                ),
             );
     }
 }
@@ -47,6 +60,8 @@ fn spawn_paddle(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     paddle_query: Query<Entity, With<Paddle>>,
+    theme: Res<Theme>,
+    skins: Res<Skins>,
     game_config: Res<GameConfig>,
 ) {
     for paddle_entity in paddle_query.iter() {
@@ -119,16 +134,20 @@ fn spawn_paddle(
         .spawn((
             Paddle,
             HorizontalVelocity::default(),
-            MaterialMesh2dBundle {
-                mesh: meshes.add(paddle_mesh.clone()).into(),
-                material: materials.add(ColorMaterial::from(PADDLE_BORDER_COLOR)),
-                transform: Transform::from_translation(Vec3::new(
+            SpatialBundle::from_transform(Transform {
+                translation: Vec3::new(
                     0.0,
-                    game_config.paddle_bottom_margin - game_config.area_height / 2.0,
+                    (game_config.paddle_bottom_margin - game_config.area_height / 2.0)
+                        * game_config.get_paddle_side_sign(),
                     2.0,
-                )),
+                ),
+                // Upside down at the top so the arc faces the bricks
+                rotation: match game_config.paddle_side {
+                    PaddleSide::Bottom => Quat::IDENTITY,
+                    PaddleSide::Top => Quat::from_rotation_z(PI),
+                },
                 ..default()
-            },
+            }),
             RigidBody::KinematicPositionBased,
             Collider::polyline(v_pos_collider, None),
             Friction::coefficient(0.0),
@@ -136,42 +155,78 @@ fn spawn_paddle(
             Ccd::enabled(),
         ))
         .with_children(|parent| {
-            parent.spawn(MaterialMesh2dBundle {
-                mesh: meshes.add(paddle_mesh).into(),
-                material: materials.add(ColorMaterial::from(PADDLE_COLOR)),
-                transform: Transform {
-                    translation: Vec3::new(0.0, PADDLE_BORDER_WIDTH, 3.0),
-                    scale: Vec3 {
-                        x: (game_config.paddle_width
-                            - PADDLE_BORDER_WIDTH
-                                * if game_config.paddle_mesh_segments > 1 {
-                                    6.0
-                                } else {
-                                    2.0
-                                })
-                            / game_config.paddle_width,
-                        y: (game_config.paddle_height - PADDLE_BORDER_WIDTH * 2.0)
-                            / game_config.paddle_height,
-                        z: 1.0,
+            if let Some(paddle_image) = &skins.paddle {
+                parent.spawn((
+                    Squash::default(),
+                    SpriteBundle {
+                        texture: paddle_image.clone(),
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::new(
+                                game_config.paddle_width,
+                                game_config.paddle_height,
+                            )),
+                            anchor: Anchor::BottomCenter,
+                            ..default()
+                        },
+                        ..default()
                     },
-                    ..default()
-                },
-                ..default()
-            });
+                ));
+                return;
+            }
+            // The meshes are on a child so squashing them leaves the collider alone
+            parent
+                .spawn((
+                    Squash::default(),
+                    MaterialMesh2dBundle {
+                        mesh: meshes.add(paddle_mesh.clone()).into(),
+                        material: materials.add(ColorMaterial::from(theme.paddle_border)),
+                        ..default()
+                    },
+                ))
+                .with_children(|visual_parent| {
+                    visual_parent.spawn(MaterialMesh2dBundle {
+                        mesh: meshes.add(paddle_mesh).into(),
+                        material: materials.add(ColorMaterial::from(theme.paddle)),
+                        transform: Transform {
+                            translation: Vec3::new(0.0, theme.paddle_border_width, 3.0),
+                            scale: Vec3 {
+                                x: (game_config.paddle_width
+                                    - theme.paddle_border_width
+                                        * if game_config.paddle_mesh_segments > 1 {
+                                            6.0
+                                        } else {
+                                            2.0
+                                        })
+                                    / game_config.paddle_width,
+                                y: (game_config.paddle_height - theme.paddle_border_width * 2.0)
+                                    / game_config.paddle_height,
+                                z: 1.0,
+                            },
+                            ..default()
+                        },
+                        ..default()
+                    });
+                });
         });
 }
 
+fn despawn_paddle(mut commands: Commands, paddle_query: Query<Entity, With<Paddle>>) {
+    for paddle_entity in paddle_query.iter() {
+        commands.entity(paddle_entity).despawn_recursive();
+    }
+}
+
 fn paddle_movement_controls(
     mut paddle_query: Query<(&mut HorizontalVelocity, &mut Transform)>,
-    walls_query: Query<(&Transform, &WallLocation), Without<HorizontalVelocity>>,
     keyboard_input: Res<Input<KeyCode>>,
+    active_profile: Res<ActiveProfile>,
     time: Res<Time>,
     game_config: Res<GameConfig>,
 ) {
     let (mut paddle_velocity, mut paddle_transform) = paddle_query.single_mut();
-    let pressed_left = keyboard_input.pressed(KeyCode::Left) || keyboard_input.pressed(KeyCode::A);
-    let pressed_right =
-        keyboard_input.pressed(KeyCode::Right) || keyboard_input.pressed(KeyCode::D);
+    let bindings = &active_profile.0.bindings;
+    let pressed_left = keyboard_input.any_pressed(bindings.left.iter().copied());
+    let pressed_right = keyboard_input.any_pressed(bindings.right.iter().copied());
 
     if pressed_left && !pressed_right {
         if paddle_velocity.value > 0.0 {
@@ -205,25 +260,13 @@ fn paddle_movement_controls(
     paddle_transform.translation.x =
         paddle_transform.translation.x + paddle_velocity.value * time.delta_seconds();
 
-    for (wall_transform, wall_location) in walls_query.iter() {
-        match wall_location {
-            WallLocation::Left => {
-                if paddle_transform.translation.x - game_config.paddle_width / 2.0
-                    < wall_transform.translation.x + wall_transform.scale.x / 2.0
-                {
-                    paddle_transform.translation.x = wall_transform.translation.x
-                        + (wall_transform.scale.x + game_config.paddle_width) / 2.0;
-                }
-            }
-            WallLocation::Right => {
-                if paddle_transform.translation.x + game_config.paddle_width / 2.0
-                    > wall_transform.translation.x - wall_transform.scale.x / 2.0
-                {
-                    paddle_transform.translation.x = wall_transform.translation.x
-                        - (wall_transform.scale.x + game_config.paddle_width) / 2.0;
-                }
-            }
-            _ => {}
-        }
-    }
+    // Stops at the sides of the arena at the height of the paddle
+    let y = paddle_transform.translation.y;
+    let base_half_width = get_arena_half_width(y, &game_config);
+    let top_half_width = get_arena_half_width(
+        y + game_config.paddle_height * game_config.get_paddle_side_sign(),
+        &game_config,
+    );
+    let max_x = (base_half_width.min(top_half_width) - game_config.paddle_width / 2.0).max(0.0);
+    paddle_transform.translation.x = paddle_transform.translation.x.clamp(-max_x, max_x);
 }
@@ -0,0 +1,456 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+};
+
+use crate::{
+    achievements::{spawn_achievement_list, Achievement},
+    history::format_duration,
+    level::EditorLevel,
+    score::Score,
+    stats::RoundStats,
+    theme::Theme,
+    AppState, ConfigFileOption, GameConfig,
+};
+
+const PROFILES_DIRECTORY: &str = "./data/profiles";
+const LAST_PROFILE_FILE: &str = "./data/last_profile.txt";
+const DEFAULT_PROFILE_NAME: &str = "Player";
+const PROFILE_NAME_MAX_LENGTH: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputBindings {
+    pub left: Vec<KeyCode>,
+    pub right: Vec<KeyCode>,
+    pub serve: Vec<KeyCode>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        InputBindings {
+            left: vec![KeyCode::Left, KeyCode::A],
+            right: vec![KeyCode::Right, KeyCode::D],
+            serve: vec![KeyCode::Up, KeyCode::W],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileStats {
+    pub games_played: i32,
+    pub games_won: i32,
+    pub bricks_destroyed: i32,
+    pub paddle_hits: i32,
+    pub time_played: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    // Config played last, offered as a shortcut on the config menu
+    pub preferred_config: Option<String>,
+    pub bindings: InputBindings,
+    // Best score per config file
+    pub high_scores: BTreeMap<String, i32>,
+    pub stats: ProfileStats,
+    pub achievements: BTreeSet<Achievement>,
+}
+
+impl Profile {
+    fn new(name: &str) -> Self {
+        Profile {
+            name: name.to_string(),
+            ..default()
+        }
+    }
+
+    fn load_all() -> Vec<Self> {
+        let Ok(entries) = fs::read_dir(PROFILES_DIRECTORY) else {
+            return Vec::new();
+        };
+
+        let mut profiles: Vec<Profile> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let contents = fs::read_to_string(&path).ok()?;
+                match serde_yaml::from_str(&contents) {
+                    Ok(profile) => Some(profile),
+                    Err(e) => {
+                        error!("Failed to parse {}: {e}", path.display());
+                        None
+                    }
+                }
+            })
+            .collect();
+        profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        profiles
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let file_path = format!("{PROFILES_DIRECTORY}/{}.yaml", self.name);
+        let contents =
+            serde_yaml::to_string(self).map_err(|e| format!("Failed to serialize profile: {e}"))?;
+        fs::create_dir_all(PROFILES_DIRECTORY)
+            .map_err(|e| format!("Failed to create {PROFILES_DIRECTORY}: {e}"))?;
+
+        fs::write(&file_path, contents).map_err(|e| format!("Failed to write {file_path}: {e}"))
+    }
+}
+
+/// The player whose bindings are used and whose records are updated.
+#[derive(Resource, Debug)]
+pub struct ActiveProfile(pub Profile);
+
+/// Name being typed for a new profile.
+#[derive(Resource, Debug, Default)]
+struct ProfileNameInput(String);
+
+#[derive(Component, Debug)]
+struct ProfileUI;
+
+#[derive(Component, Debug)]
+enum ProfileButton {
+    Select(String),
+    New,
+}
+
+pub struct ProfilePlugin;
+
+impl Plugin for ProfilePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ActiveProfile(load_last_profile()))
+            .add_systems(OnEnter(AppState::SelectConfig), spawn_profile_ui)
+            .add_systems(
+                OnExit(AppState::SelectConfig),
+                (despawn_profile_ui, cancel_profile_name_input),
+            )
+            .add_systems(
+                Update,
+                (
+                    handle_profile_click,
+                    type_profile_name,
+                    (despawn_profile_ui, spawn_profile_ui).run_if(
+                        resource_changed::<ActiveProfile>()
+                            .or_else(resource_changed::<Theme>())
+                            .or_else(resource_exists_and_changed::<ProfileNameInput>())
+                            .or_else(resource_removed::<ProfileNameInput>()),
+                    ),
+                )
+                    .chain()
+                    .run_if(in_state(AppState::SelectConfig)),
+            )
+            // Editor play-tests count neither for high scores nor for the totals
+            .add_systems(
+                OnEnter(AppState::GaveOver),
+                record_profile_game.run_if(not(resource_exists::<EditorLevel>())),
+            );
+    }
+}
+
+fn load_last_profile() -> Profile {
+    let last_name = fs::read_to_string(LAST_PROFILE_FILE).unwrap_or_default();
+    let mut profiles = Profile::load_all();
+    if let Some(index) = profiles
+        .iter()
+        .position(|profile| profile.name == last_name.trim())
+    {
+        return profiles.swap_remove(index);
+    }
+    if !profiles.is_empty() {
+        return profiles.swap_remove(0);
+    }
+
+    let profile = Profile::new(DEFAULT_PROFILE_NAME);
+    if let Err(e) = profile.save() {
+        error!("{e}");
+    }
+    profile
+}
+
+fn set_active_profile(active_profile: &mut ActiveProfile, profile: Profile) {
+    if let Err(e) = fs::write(LAST_PROFILE_FILE, &profile.name) {
+        error!("Failed to write {LAST_PROFILE_FILE}: {e}");
+    }
+    active_profile.0 = profile;
+}
+
+fn spawn_profile_ui(
+    mut commands: Commands,
+    active_profile: Res<ActiveProfile>,
+    profile_name_input: Option<Res<ProfileNameInput>>,
+    theme: Res<Theme>,
+) {
+    let profile = &active_profile.0;
+    let text_style = |font_size: f32| TextStyle {
+        font_size,
+        color: theme.text,
+        ..default()
+    };
+    let high_scores = profile
+        .high_scores
+        .iter()
+        .map(|(config, score)| format!("{config}: {score}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    display: Display::Flex,
+                    flex_direction: FlexDirection::Column,
+                    left: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    max_width: Val::Percent(40.0),
+                    row_gap: Val::Px(6.0),
+                    ..default()
+                },
+                ..default()
+            },
+            ProfileUI,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        display: Display::Flex,
+                        flex_wrap: FlexWrap::Wrap,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(6.0),
+                        row_gap: Val::Px(6.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section("Player:", text_style(20.0)));
+                    for other_profile in Profile::load_all() {
+                        let active = other_profile.name == profile.name;
+                        spawn_profile_button(
+                            parent,
+                            &theme,
+                            ProfileButton::Select(other_profile.name),
+                            active,
+                        );
+                    }
+                    spawn_profile_button(parent, &theme, ProfileButton::New, false);
+                });
+
+            if let Some(profile_name_input) = profile_name_input {
+                parent.spawn(TextBundle::from_section(
+                    format!("New player name: {}_  [Enter] create", profile_name_input.0),
+                    text_style(20.0),
+                ));
+            }
+
+            parent.spawn(TextBundle::from_section(
+                format!(
+                    "Games: {}, won: {}, bricks: {}, paddle hits: {}, time played: {}\n\
+                     Best scores: {}",
+                    profile.stats.games_played,
+                    profile.stats.games_won,
+                    profile.stats.bricks_destroyed,
+                    profile.stats.paddle_hits,
+                    format_duration(profile.stats.time_played),
+                    if high_scores.is_empty() {
+                        "none yet"
+                    } else {
+                        high_scores.as_str()
+                    },
+                ),
+                text_style(18.0),
+            ));
+            spawn_achievement_list(parent, &theme, profile);
+
+            let preferred_config = profile
+                .preferred_config
+                .as_ref()
+                .filter(|preferred_config| {
+                    Path::new(&format!("./assets/{preferred_config}")).exists()
+                });
+            if let Some(preferred_config) = preferred_config {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                align_self: AlignSelf::FlexStart,
+                                padding: UiRect::all(Val::Px(8.0)),
+                                ..default()
+                            },
+                            background_color: theme.button.into(),
+                            ..default()
+                        },
+                        ConfigFileOption(preferred_config.clone(), AppState::InGame),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(
+                            format!("Play {preferred_config}"),
+                            text_style(20.0),
+                        ));
+                    });
+            }
+        });
+}
+
+fn spawn_profile_button(
+    parent: &mut ChildBuilder,
+    theme: &Theme,
+    button: ProfileButton,
+    active: bool,
+) {
+    let label = match &button {
+        ProfileButton::Select(name) => name.clone(),
+        ProfileButton::New => "+ New".to_string(),
+    };
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::all(Val::Px(6.0)),
+                    ..default()
+                },
+                background_color: if active {
+                    theme.button_active.into()
+                } else {
+                    theme.button.into()
+                },
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font_size: 20.0,
+                    color: theme.text,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn despawn_profile_ui(mut commands: Commands, ui_query: Query<Entity, With<ProfileUI>>) {
+    for entity in ui_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn handle_profile_click(
+    mut commands: Commands,
+    mut button_query: Query<
+        (&mut BackgroundColor, &ProfileButton, &Interaction),
+        Changed<Interaction>,
+    >,
+    mut active_profile: ResMut<ActiveProfile>,
+    theme: Res<Theme>,
+) {
+    for (mut background_color, button, interaction) in button_query.iter_mut() {
+        match (interaction, button) {
+            (Interaction::Pressed, ProfileButton::Select(name)) => {
+                if *name == active_profile.0.name {
+                    continue;
+                }
+                if let Some(profile) = Profile::load_all()
+                    .into_iter()
+                    .find(|profile| profile.name == *name)
+                {
+                    set_active_profile(&mut active_profile, profile);
+                }
+            }
+            (Interaction::Pressed, ProfileButton::New) => {
+                commands.insert_resource(ProfileNameInput::default());
+            }
+            (Interaction::Hovered, _) => {
+                background_color.0 = theme.button_hover;
+            }
+            (Interaction::None, ProfileButton::Select(name)) if *name == active_profile.0.name => {
+                background_color.0 = theme.button_active;
+            }
+            (Interaction::None, _) => {
+                background_color.0 = theme.button;
+            }
+        }
+    }
+}
+
+fn cancel_profile_name_input(mut commands: Commands) {
+    commands.remove_resource::<ProfileNameInput>();
+}
+
+fn type_profile_name(
+    mut commands: Commands,
+    mut received_characters: EventReader<ReceivedCharacter>,
+    keyboard_input: Res<Input<KeyCode>>,
+    profile_name_input: Option<ResMut<ProfileNameInput>>,
+    mut active_profile: ResMut<ActiveProfile>,
+) {
+    let Some(mut profile_name_input) = profile_name_input else {
+        received_characters.clear();
+        return;
+    };
+
+    for event in received_characters.read() {
+        // Names are used as file names, so only keep characters that are safe there
+        if (event.char.is_alphanumeric() || event.char == '-' || event.char == '_')
+            && profile_name_input.0.chars().count() < PROFILE_NAME_MAX_LENGTH
+        {
+            profile_name_input.0.push(event.char);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        profile_name_input.0.pop();
+    }
+    if !keyboard_input.any_just_pressed([KeyCode::Return, KeyCode::NumpadEnter]) {
+        return;
+    }
+
+    commands.remove_resource::<ProfileNameInput>();
+    let name = profile_name_input.0.clone();
+    if name.is_empty() {
+        return;
+    }
+    let profile = Profile::load_all()
+        .into_iter()
+        .find(|profile| profile.name == name)
+        .unwrap_or_else(|| Profile::new(&name));
+    if let Err(e) = profile.save() {
+        error!("{e}");
+        return;
+    }
+    set_active_profile(&mut active_profile, profile);
+}
+
+fn record_profile_game(
+    mut active_profile: ResMut<ActiveProfile>,
+    score: Res<Score>,
+    round_stats: Res<RoundStats>,
+    game_config: Res<GameConfig>,
+) {
+    let profile = &mut active_profile.0;
+    profile.preferred_config = Some(game_config.file_name.clone());
+    let high_score = profile
+        .high_scores
+        .entry(game_config.file_name.clone())
+        .or_insert(score.score);
+    *high_score = (*high_score).max(score.score);
+
+    profile.stats.games_played += 1;
+    if round_stats.won {
+        profile.stats.games_won += 1;
+    }
+    profile.stats.bricks_destroyed += round_stats.bricks_destroyed();
+    profile.stats.paddle_hits += round_stats.paddle_hits;
+    profile.stats.time_played += round_stats.time_played;
+
+    if let Err(e) = profile.save() {
+        error!("{e}");
+    }
+}
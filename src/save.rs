@@ -0,0 +1,276 @@
+use bevy::{app::AppExit, prelude::*};
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{fs, time::Duration};
+
+use crate::{
+    achievements::AchievementTracker,
+    ball::{Ball, BallAirTime, BallTopSpeed, BallTrajectory, Serving},
+    bricks::{spawn_brick, Brick, LayoutSeed},
+    level::LevelBrick,
+    paddle::Paddle,
+    score::{Combo, Score, ScoreBreakdown, ScoreDecayProgress, ScoreLossTimer},
+    stats::{RoundStats, DATA_DIRECTORY},
+    theme::Theme,
+    AppState, ConfigFileOption, GameConfig,
+};
+
+const SAVE_FILE: &str = "save.yaml";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedBrick {
+    #[serde(flatten)]
+    brick: LevelBrick,
+    score: i32,
+}
+
+/// Round left with Escape, offered as "Continue" on the config menu.
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+pub struct SavedRound {
+    pub config: String,
+    seed: Option<u64>,
+    bricks: Vec<SavedBrick>,
+    ball_position: [f32; 2],
+    ball_velocity: [f32; 2],
+    ball_top_speed: f32,
+    ball_score_gained: i32,
+    ball_bricks_destroyed: i32,
+    #[serde(default)]
+    ball_air_time: f32,
+    #[serde(default)]
+    ball_nudges: i32,
+    serving: bool,
+    // Seconds the serve timeout had run, when it is set
+    serve_elapsed: Option<f32>,
+    paddle_x: f32,
+    score: i32,
+    score_breakdown: ScoreBreakdown,
+    combo: Combo,
+    score_decay_progress: ScoreDecayProgress,
+    score_loss_elapsed: f32,
+    #[serde(default)]
+    round_stats: RoundStats,
+    #[serde(default)]
+    achievement_tracker: AchievementTracker,
+}
+
+impl SavedRound {
+    pub fn load() -> Result<Self, String> {
+        let file_path = format!("{DATA_DIRECTORY}/{SAVE_FILE}");
+        let contents = fs::read_to_string(&file_path)
+            .map_err(|e| format!("Failed to read {file_path}: {e}"))?;
+
+        serde_yaml::from_str(&contents).map_err(|e| format!("Failed to parse {file_path}: {e}"))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let file_path = format!("{DATA_DIRECTORY}/{SAVE_FILE}");
+        let contents =
+            serde_yaml::to_string(self).map_err(|e| format!("Failed to serialize round: {e}"))?;
+        fs::create_dir_all(DATA_DIRECTORY)
+            .map_err(|e| format!("Failed to create {DATA_DIRECTORY}: {e}"))?;
+
+        fs::write(&file_path, contents).map_err(|e| format!("Failed to write {file_path}: {e}"))
+    }
+
+    fn remove() {
+        let _ = fs::remove_file(format!("{DATA_DIRECTORY}/{SAVE_FILE}"));
+    }
+}
+
+/// Marks the config menu button that resumes the saved round.
+#[derive(Component, Debug)]
+pub struct ContinueRound;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            handle_continue_click.run_if(in_state(AppState::SelectConfig)),
+        )
+        .add_systems(
+            Update,
+            restore_round
+                .run_if(in_state(AppState::InGame).and_then(resource_exists::<SavedRound>())),
+        )
+        .add_systems(
+            PostUpdate,
+            save_round_on_exit.run_if(in_state(AppState::InGame)),
+        );
+    }
+}
+
+fn handle_continue_click(
+    mut commands: Commands,
+    config_query: Query<
+        (&Interaction, &ConfigFileOption, Has<ContinueRound>),
+        Changed<Interaction>,
+    >,
+) {
+    for (interaction, config_file, continue_round) in config_query.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if !continue_round {
+            // A fresh round replaces the saved one
+            if config_file.1 == AppState::InGame {
+                SavedRound::remove();
+            }
+            continue;
+        }
+        // The config itself is loaded by the config menu like any other button
+        match SavedRound::load() {
+            Ok(saved_round) => commands.insert_resource(saved_round),
+            Err(e) => error!("{e}"),
+        }
+    }
+}
+
+/// Replaces the freshly spawned round with the saved one, once the round entities exist.
+fn restore_round(
+    mut commands: Commands,
+    saved_round: Res<SavedRound>,
+    mut ball_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut BallTopSpeed,
+            Option<&mut Serving>,
+        ),
+        (With<Ball>, Without<Paddle>),
+    >,
+    mut paddle_query: Query<&mut Transform, (With<Paddle>, Without<Ball>)>,
+    mut ball_air_time: ResMut<BallAirTime>,
+    mut ball_trajectory: ResMut<BallTrajectory>,
+    mut score: ResMut<Score>,
+    mut score_breakdown: ResMut<ScoreBreakdown>,
+    mut combo: ResMut<Combo>,
+    mut score_decay_progress: ResMut<ScoreDecayProgress>,
+    mut score_loss_timer: ResMut<ScoreLossTimer>,
+    mut round_stats: ResMut<RoundStats>,
+    mut achievement_tracker: ResMut<AchievementTracker>,
+    theme: Res<Theme>,
+    game_config: Res<GameConfig>,
+) {
+    let Ok((ball_entity, mut ball_transform, mut ball_velocity, mut ball_top_speed, serving)) =
+        ball_query.get_single_mut()
+    else {
+        return;
+    };
+
+    for saved_brick in saved_round.bricks.iter() {
+        let brick_entity = spawn_brick(&mut commands, &saved_brick.brick, &theme, &game_config);
+        commands.entity(brick_entity).insert(Brick {
+            score: saved_brick.score,
+            size: saved_brick.brick.size,
+            brick_type: saved_brick.brick.brick_type,
+        });
+    }
+    match saved_round.seed {
+        Some(seed) => commands.insert_resource(LayoutSeed { seed }),
+        None => commands.remove_resource::<LayoutSeed>(),
+    }
+
+    ball_transform.translation.x = saved_round.ball_position[0];
+    ball_transform.translation.y = saved_round.ball_position[1];
+    ball_velocity.linvel = Vec2::from(saved_round.ball_velocity);
+    ball_top_speed.speed = saved_round.ball_top_speed;
+    ball_top_speed.score_gained = saved_round.ball_score_gained;
+    ball_top_speed.bricks_destroyed = saved_round.ball_bricks_destroyed;
+    ball_air_time.0 = saved_round.ball_air_time;
+    ball_trajectory.nudges = saved_round.ball_nudges;
+    if !saved_round.serving {
+        commands.entity(ball_entity).remove::<Serving>();
+    } else if let (Some(mut serving), Some(elapsed)) = (serving, saved_round.serve_elapsed) {
+        if let Some(timer) = &mut serving.timeout {
+            timer.tick(Duration::from_secs_f32(elapsed));
+        }
+    }
+    for mut paddle_transform in paddle_query.iter_mut() {
+        paddle_transform.translation.x = saved_round.paddle_x;
+    }
+
+    score.score = saved_round.score;
+    *score_breakdown = saved_round.score_breakdown.clone();
+    *combo = saved_round.combo.clone();
+    *score_decay_progress = saved_round.score_decay_progress.clone();
+    score_loss_timer.0.reset();
+    score_loss_timer
+        .0
+        .tick(Duration::from_secs_f32(saved_round.score_loss_elapsed));
+    *round_stats = saved_round.round_stats.clone();
+    *achievement_tracker = saved_round.achievement_tracker.clone();
+
+    commands.remove_resource::<SavedRound>();
+    SavedRound::remove();
+}
+
+fn save_round_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    brick_query: Query<(&Brick, &Transform)>,
+    ball_query: Query<(&Transform, &Velocity, &BallTopSpeed, Option<&Serving>), With<Ball>>,
+    paddle_query: Query<&Transform, With<Paddle>>,
+    ball_air_time: Res<BallAirTime>,
+    ball_trajectory: Res<BallTrajectory>,
+    score: Res<Score>,
+    score_breakdown: Res<ScoreBreakdown>,
+    combo: Res<Combo>,
+    score_decay_progress: Res<ScoreDecayProgress>,
+    score_loss_timer: Res<ScoreLossTimer>,
+    round_stats: Res<RoundStats>,
+    achievement_tracker: Res<AchievementTracker>,
+    layout_seed: Option<Res<LayoutSeed>>,
+    game_config: Res<GameConfig>,
+) {
+    if exit_events.read().count() == 0 {
+        return;
+    }
+    let (Ok((ball_transform, ball_velocity, ball_top_speed, serving)), Ok(paddle_transform)) =
+        (ball_query.get_single(), paddle_query.get_single())
+    else {
+        return;
+    };
+
+    let saved_round = SavedRound {
+        config: game_config.file_name.clone(),
+        seed: layout_seed.map(|layout_seed| layout_seed.seed),
+        bricks: brick_query
+            .iter()
+            .map(|(brick, transform)| SavedBrick {
+                brick: LevelBrick {
+                    x: transform.translation.x,
+                    y: transform.translation.y,
+                    size: brick.size,
+                    brick_type: brick.brick_type,
+                },
+                score: brick.score,
+            })
+            .collect(),
+        ball_position: ball_transform.translation.truncate().to_array(),
+        ball_velocity: ball_velocity.linvel.to_array(),
+        ball_top_speed: ball_top_speed.speed,
+        ball_score_gained: ball_top_speed.score_gained,
+        ball_bricks_destroyed: ball_top_speed.bricks_destroyed,
+        ball_air_time: ball_air_time.0,
+        ball_nudges: ball_trajectory.nudges,
+        serving: serving.is_some(),
+        serve_elapsed: serving
+            .and_then(|serving| serving.timeout.as_ref())
+            .map(|timer| timer.elapsed_secs()),
+        paddle_x: paddle_transform.translation.x,
+        score: score.score,
+        score_breakdown: score_breakdown.clone(),
+        combo: combo.clone(),
+        score_decay_progress: score_decay_progress.clone(),
+        score_loss_elapsed: score_loss_timer.0.elapsed_secs(),
+        round_stats: round_stats.clone(),
+        achievement_tracker: achievement_tracker.clone(),
+    };
+    match saved_round.save() {
+        Ok(()) => info!("saved round with {} bricks", saved_round.bricks.len()),
+        Err(e) => error!("{e}"),
+    }
+}
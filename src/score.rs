@@ -1,6 +1,17 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::{AppState, GameConfig};
+use crate::{
+    ball::BallTrajectory,
+    bricks::{describe_layout, Brick, LayoutSeed, RetryLayout},
+    events::{BallLost, BrickDestroyed, FieldCleared, GameplaySet, PaddleHit},
+    history::format_duration,
+    layout_debug::LayoutDebugger,
+    level::EditorLevel,
+    stats::RoundStats,
+    theme::Theme,
+    AppState, GameConfig, ScoreDecay,
+};
 
 // pub const BRICK_MAX_SCORE: i32 = 10;
 // pub const WIN_SCORE_BONUS: i32 = 500;
@@ -12,34 +23,90 @@ pub struct Score {
     pub score: i32,
 }
 
+/// Where the points of the current game came from.
+#[derive(Resource, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ScoreBreakdown {
+    pub bricks: i32,
+    pub combo_bonus: i32,
+    pub region_bonus: i32,
+    pub win_bonus: i32,
+    pub time_bonus: i32,
+    pub time_penalty: i32,
+}
+
+/// Bricks destroyed since the ball last touched the paddle.
+#[derive(Resource, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Combo {
+    pub streak: i32,
+    pub longest: i32,
+}
+
 #[derive(Component, Debug)]
 pub struct ScoreText;
 
 #[derive(Resource, Debug)]
-struct ScoreLossTimer(Timer);
+pub struct ScoreLossTimer(pub Timer);
 
-#[derive(Resource, Debug)]
-pub struct GameNumber {
-    pub number: usize,
+/// Score loss ticks elapsed this game and what is left of the countdown time bonus.
+#[derive(Resource, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ScoreDecayProgress {
+    pub time_bonus: i32,
+    ticks: i32,
 }
 
 #[derive(Component, Debug)]
 pub struct FinalScoreDisplay;
 
+#[derive(Component, Debug, Clone, Copy)]
+enum GameOverButton {
+    RetrySeed,
+    NewLayout,
+    ChangeConfig,
+}
+
+impl GameOverButton {
+    fn label(&self) -> &'static str {
+        match self {
+            GameOverButton::RetrySeed => "Retry same seed",
+            GameOverButton::NewLayout => "New layout",
+            GameOverButton::ChangeConfig => "Change config",
+        }
+    }
+}
+
 pub struct ScorePlugin;
 
 impl Plugin for ScorePlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Score { score: 0 })
-            .insert_resource(GameNumber { number: 1 })
+            .insert_resource(ScoreBreakdown::default())
+            .insert_resource(Combo::default())
+            .insert_resource(ScoreDecayProgress::default())
             .add_systems(OnEnter(AppState::InGame), spawn_score_display)
+            .add_systems(OnEnter(AppState::InGame), reset_score_loss)
             .add_systems(
                 Update,
                 update_score_display.run_if(in_state(AppState::InGame)),
             )
-            .add_systems(Update, score_loss.run_if(in_state(AppState::InGame)))
+            .add_systems(
+                Update,
+                score_loss.run_if(
+                    in_state(AppState::InGame).and_then(not(resource_exists::<LayoutDebugger>())),
+                ),
+            )
+            .add_systems(
+                Update,
+                (score_gameplay_events, end_round)
+                    .in_set(GameplaySet::Consequences)
+                    .run_if(in_state(AppState::InGame)),
+            )
             .add_systems(OnEnter(AppState::GaveOver), spawn_final_score_display)
-            .add_systems(OnExit(AppState::GaveOver), spawn_previous_score_display)
+            .add_systems(
+                Update,
+                handle_game_over_click.run_if(in_state(AppState::GaveOver)),
+            )
+            .add_systems(OnExit(AppState::GaveOver), close_final_score_display)
+            .add_systems(OnEnter(AppState::SelectConfig), despawn_score_display)
             .add_systems(Update, start_next_game);
     }
 }
@@ -47,20 +114,39 @@ impl Plugin for ScorePlugin {
 fn spawn_score_display(
     mut commands: Commands,
     score_text_query: Query<With<ScoreText>>,
-    game_config: Res<GameConfig>,
+    theme: Res<Theme>,
 ) {
     for _ in score_text_query.iter() {
         return;
     }
 
     commands.spawn((
-        TextBundle::from_section(
-            format!("Score: 0"),
-            TextStyle {
-                font_size: 32.0,
-                ..default()
-            },
-        )
+        TextBundle::from_sections([
+            TextSection::new(
+                format!("Score: 0"),
+                TextStyle {
+                    font_size: 32.0,
+                    color: theme.text,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "",
+                TextStyle {
+                    font_size: 24.0,
+                    color: theme.text_highlight,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "",
+                TextStyle {
+                    font_size: 24.0,
+                    color: theme.text,
+                    ..default()
+                },
+            ),
+        ])
         .with_text_alignment(TextAlignment::Center)
         .with_style(Style {
             position_type: PositionType::Absolute,
@@ -70,35 +156,174 @@ fn spawn_score_display(
         }),
         ScoreText,
     ));
+}
 
+fn reset_score_loss(mut commands: Commands, game_config: Res<GameConfig>) {
     commands.insert_resource(ScoreLossTimer(Timer::from_seconds(
         game_config.score_loss_interval,
         TimerMode::Repeating,
     )));
+    commands.insert_resource(ScoreDecayProgress {
+        time_bonus: match game_config.score_decay {
+            ScoreDecay::Countdown => game_config.score_time_bonus,
+            _ => 0,
+        },
+        ticks: 0,
+    });
 }
 
-fn update_score_display(mut query: Query<&mut Text, With<ScoreText>>, score: Res<Score>) {
+fn update_score_display(
+    mut query: Query<&mut Text, With<ScoreText>>,
+    score: Res<Score>,
+    combo: Res<Combo>,
+    score_decay_progress: Res<ScoreDecayProgress>,
+    game_config: Res<GameConfig>,
+) {
     for mut text in query.iter_mut() {
         // Update the text based on the score resource
         text.sections[0].value = format!("Score: {}", score.score);
+        text.sections[1].value = if combo.streak > 1 {
+            format!(
+                "  x{:.2} streak {}",
+                game_config.get_combo_multiplier(combo.streak),
+                combo.streak
+            )
+        } else {
+            String::new()
+        };
+        text.sections[2].value = match game_config.score_decay {
+            ScoreDecay::Countdown => {
+                format!("\nTime bonus: {}", score_decay_progress.time_bonus)
+            }
+            _ => String::new(),
+        };
     }
 }
 
 fn score_loss(
     time: Res<Time>,
     mut score: ResMut<Score>,
+    mut score_breakdown: ResMut<ScoreBreakdown>,
+    mut score_decay_progress: ResMut<ScoreDecayProgress>,
     mut timer: ResMut<ScoreLossTimer>,
     game_config: Res<GameConfig>,
 ) {
-    if timer.0.tick(time.delta()).just_finished() {
-        score.score -= game_config.score_loss;
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
     }
+
+    if game_config.score_decay == ScoreDecay::Countdown {
+        score_decay_progress.time_bonus =
+            (score_decay_progress.time_bonus - game_config.score_loss).max(0);
+    }
+    let loss = game_config.get_score_loss(score_decay_progress.ticks);
+    score_decay_progress.ticks += 1;
+
+    let new_score = game_config.get_decayed_score(score.score, loss);
+    score_breakdown.time_penalty += score.score - new_score;
+    score.score = new_score;
 }
 
-fn spawn_final_score_display(mut commands: Commands, score: Res<Score>) {
+fn score_gameplay_events(
+    mut brick_destroyed_events: EventReader<BrickDestroyed>,
+    mut paddle_hit_events: EventReader<PaddleHit>,
+    mut field_cleared_events: EventReader<FieldCleared>,
+    brick_query: Query<(Entity, &Transform), With<Brick>>,
+    mut score: ResMut<Score>,
+    mut score_breakdown: ResMut<ScoreBreakdown>,
+    mut combo: ResMut<Combo>,
+    mut round_stats: ResMut<RoundStats>,
+    score_decay_progress: Res<ScoreDecayProgress>,
+    game_config: Res<GameConfig>,
+) {
+    // Paddle hits and bricks of the same frame are not ordered, the paddle resets the streak first
+    if paddle_hit_events.read().count() > 0 {
+        combo.streak = 0;
+    }
+
+    let destroyed_bricks: Vec<&BrickDestroyed> = brick_destroyed_events.read().collect();
+    for (index, brick_destroyed) in destroyed_bricks.iter().enumerate() {
+        combo.streak += 1;
+        combo.longest = combo.longest.max(combo.streak);
+        let brick_score = (brick_destroyed.score as f32
+            * game_config.get_combo_multiplier(combo.streak))
+        .round() as i32;
+        score.score += brick_score;
+        score_breakdown.bricks += brick_destroyed.score;
+        score_breakdown.combo_bonus += brick_score - brick_destroyed.score;
+        // The stats keep the awarded points, the event only has the base score of the brick
+        round_stats.record_brick(brick_destroyed.size, brick_score);
+
+        // Despawns are deferred, so bricks destroyed this frame are still in the query
+        let Some(region) = game_config.get_brick_region(brick_destroyed.position) else {
+            continue;
+        };
+        let region_cleared = brick_query
+            .iter()
+            .filter(|(entity, _)| {
+                !destroyed_bricks[..=index]
+                    .iter()
+                    .any(|destroyed| destroyed.entity == *entity)
+            })
+            .all(|(_, transform)| {
+                game_config.get_brick_region(transform.translation.truncate()) != Some(region)
+            });
+        if region_cleared {
+            score.score += game_config.region_clear_bonus;
+            score_breakdown.region_bonus += game_config.region_clear_bonus;
+        }
+    }
+
+    if field_cleared_events.read().count() > 0 {
+        score.score += game_config.win_score_bonus;
+        score_breakdown.win_bonus += game_config.win_score_bonus;
+        score.score += score_decay_progress.time_bonus;
+        score_breakdown.time_bonus += score_decay_progress.time_bonus;
+    }
+}
+
+fn end_round(
+    mut ball_lost_events: EventReader<BallLost>,
+    mut field_cleared_events: EventReader<FieldCleared>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if ball_lost_events.read().count() > 0 || field_cleared_events.read().count() > 0 {
+        next_state.set(AppState::GaveOver);
+    }
+}
+
+fn spawn_final_score_display(
+    mut commands: Commands,
+    mut window_query: Query<&mut Window>,
+    score: Res<Score>,
+    score_breakdown: Res<ScoreBreakdown>,
+    combo: Res<Combo>,
+    ball_trajectory: Res<BallTrajectory>,
+    round_stats: Res<RoundStats>,
+    layout_seed: Option<Res<LayoutSeed>>,
+    editor_level: Option<Res<EditorLevel>>,
+    theme: Res<Theme>,
+    game_config: Res<GameConfig>,
+) {
+    window_query.single_mut().cursor.visible = true;
+
+    let bricks_by_size = round_stats
+        .bricks_by_size
+        .iter()
+        .map(|(size, count)| format!("{size}px x{count}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let layout = describe_layout(layout_seed.as_deref(), editor_level.is_some(), &game_config);
+    let text_style = |font_size: f32| TextStyle {
+        font_size,
+        color: theme.final_score_text,
+        ..default()
+    };
+
     commands
         .spawn(NodeBundle {
             style: Style {
+                position_type: PositionType::Absolute,
                 display: Display::Flex,
                 align_items: AlignItems::Center,
                 justify_content: JustifyContent::Center,
@@ -106,65 +331,179 @@ fn spawn_final_score_display(mut commands: Commands, score: Res<Score>) {
                 height: Val::Percent(100.0),
                 ..default()
             },
+            z_index: ZIndex::Global(1),
             ..default()
         })
         .with_children(|parent| {
-            parent.spawn(
-                TextBundle::from_section(
-                    format!("Final Score: {}\nPress space to play again", score.score),
-                    TextStyle {
-                        font_size: 64.0,
-                        color: Color::BLACK,
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        display: Display::Flex,
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        row_gap: Val::Px(12.0),
+                        width: Val::Percent(55.0),
+                        max_height: Val::Percent(90.0),
+                        padding: UiRect::all(Val::Px(20.0)),
                         ..default()
                     },
-                )
-                .with_text_alignment(TextAlignment::Center),
-            );
+                    background_color: theme.final_score_panel.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(
+                        TextBundle::from_sections([
+                            TextSection::new(
+                                if round_stats.won {
+                                    "You won!\n"
+                                } else {
+                                    "Game over\n"
+                                },
+                                text_style(48.0),
+                            ),
+                            TextSection::new(
+                                format!("Final Score: {}", score.score),
+                                text_style(64.0),
+                            ),
+                        ])
+                        .with_text_alignment(TextAlignment::Center),
+                    );
+                    parent.spawn(
+                        TextBundle::from_section(
+                            format!(
+                                "Time played: {}\n\
+                                 Bricks destroyed: {} ({})\n\
+                                 Paddle hits: {}, max ball speed: {:.0}\n\
+                                 Longest streak: {}, trajectory nudges: {}\n\
+                                 Bricks {} + combo {} + regions {} + win {} + time bonus {} \
+                                 - time {}\nScore decay: {}\n\
+                                 Config: {}, {}",
+                                format_duration(round_stats.time_played),
+                                round_stats.bricks_destroyed(),
+                                bricks_by_size,
+                                round_stats.paddle_hits,
+                                round_stats.max_ball_speed,
+                                combo.longest,
+                                ball_trajectory.nudges,
+                                score_breakdown.bricks,
+                                score_breakdown.combo_bonus,
+                                score_breakdown.region_bonus,
+                                score_breakdown.win_bonus,
+                                score_breakdown.time_bonus,
+                                score_breakdown.time_penalty,
+                                game_config.describe_score_decay(),
+                                game_config.file_name,
+                                layout,
+                            ),
+                            text_style(24.0),
+                        )
+                        .with_text_alignment(TextAlignment::Center),
+                    );
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                display: Display::Flex,
+                                flex_wrap: FlexWrap::Wrap,
+                                justify_content: JustifyContent::Center,
+                                column_gap: Val::Px(12.0),
+                                row_gap: Val::Px(12.0),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for button in [
+                                GameOverButton::RetrySeed,
+                                GameOverButton::NewLayout,
+                                GameOverButton::ChangeConfig,
+                            ] {
+                                spawn_game_over_button(parent, &theme, button);
+                            }
+                        });
+                    parent.spawn(TextBundle::from_section(
+                        "Press space to play again",
+                        text_style(24.0),
+                    ));
+                });
         })
         .insert(FinalScoreDisplay);
+}
 
-    commands.spawn((
-        SpriteBundle {
-            sprite: Sprite {
-                color: Color::rgb(191.0 / 255.0, 148.0 / 255.0, 228.0 / 255.0),
+fn spawn_game_over_button(parent: &mut ChildBuilder, theme: &Theme, button: GameOverButton) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                background_color: theme.button.into(),
                 ..default()
             },
-            transform: Transform {
-                scale: Vec3::new(900.0, 200.0, 1.0),
-                translation: Vec3::new(0.0, 0.0, 2.0),
-                ..default()
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                button.label(),
+                TextStyle {
+                    font_size: 28.0,
+                    color: theme.text,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn handle_game_over_click(
+    mut commands: Commands,
+    mut button_query: Query<
+        (&mut BackgroundColor, &GameOverButton, &Interaction),
+        Changed<Interaction>,
+    >,
+    mut next_state: ResMut<NextState<AppState>>,
+    theme: Res<Theme>,
+) {
+    for (mut background_color, button, interaction) in button_query.iter_mut() {
+        match interaction {
+            Interaction::Pressed => match button {
+                GameOverButton::RetrySeed => {
+                    commands.insert_resource(RetryLayout);
+                    next_state.set(AppState::InGame);
+                }
+                GameOverButton::NewLayout => next_state.set(AppState::InGame),
+                GameOverButton::ChangeConfig => next_state.set(AppState::SelectConfig),
             },
-            ..default()
-        },
-        FinalScoreDisplay,
-    ));
+            Interaction::Hovered => {
+                background_color.0 = theme.button_hover;
+            }
+            Interaction::None => {
+                background_color.0 = theme.button;
+            }
+        }
+    }
+}
+
+fn despawn_score_display(mut commands: Commands, score_text_query: Query<Entity, With<ScoreText>>) {
+    for entity in score_text_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
 }
 
-fn spawn_previous_score_display(
+fn close_final_score_display(
     mut commands: Commands,
+    mut window_query: Query<&mut Window>,
     mut score: ResMut<Score>,
-    mut game_number: ResMut<GameNumber>,
+    mut score_breakdown: ResMut<ScoreBreakdown>,
+    mut combo: ResMut<Combo>,
     final_score_display: Query<Entity, With<FinalScoreDisplay>>,
 ) {
+    window_query.single_mut().cursor.visible = false;
     for entity in final_score_display.iter() {
         commands.entity(entity).despawn_recursive();
     }
-    commands.spawn((TextBundle::from_section(
-        format!("Game {}: {}", game_number.number, score.score),
-        TextStyle {
-            font_size: 24.0,
-            ..default()
-        },
-    )
-    .with_text_alignment(TextAlignment::Center)
-    .with_style(Style {
-        position_type: PositionType::Absolute,
-        right: Val::Px(5.0),
-        top: Val::Px(5.0 + (game_number.number - 1) as f32 * 36.0),
-        ..default()
-    }),));
-    game_number.number += 1;
     score.score = 0;
+    *score_breakdown = ScoreBreakdown::default();
+    *combo = Combo::default();
 }
 
 fn start_next_game(
@@ -173,9 +512,10 @@ fn start_next_game(
     keyboard_input: Res<Input<KeyCode>>,
 ) {
     if keyboard_input.any_just_pressed([KeyCode::Space, KeyCode::Return, KeyCode::NumpadEnter]) {
-        next_state.set(match state.get() {
-            AppState::InGame => AppState::GaveOver,
-            _ => AppState::InGame,
-        });
+        match state.get() {
+            AppState::InGame => next_state.set(AppState::GaveOver),
+            AppState::GaveOver => next_state.set(AppState::InGame),
+            _ => {}
+        }
     }
 }
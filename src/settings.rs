@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs;
+
+use crate::{stats::DATA_DIRECTORY, theme::Theme};
+
+/// Reads settings from the data directory, the defaults when the file is missing or invalid.
+pub fn load_settings<T: DeserializeOwned + Default>(file_name: &str) -> T {
+    fs::read_to_string(format!("{DATA_DIRECTORY}/{file_name}"))
+        .ok()
+        .and_then(|contents| serde_yaml::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_settings<T: Serialize>(file_name: &str, settings: &T) -> Result<(), String> {
+    let file_path = format!("{DATA_DIRECTORY}/{file_name}");
+    let contents = serde_yaml::to_string(settings)
+        .map_err(|e| format!("Failed to serialize {file_name}: {e}"))?;
+    fs::create_dir_all(DATA_DIRECTORY)
+        .map_err(|e| format!("Failed to create {DATA_DIRECTORY}: {e}"))?;
+
+    fs::write(&file_path, contents).map_err(|e| format!("Failed to write {file_path}: {e}"))
+}
+
+/// Button of a settings panel on the config menu, `button` tells its click handler what it does.
+pub fn spawn_settings_button<B: Component>(
+    parent: &mut ChildBuilder,
+    theme: &Theme,
+    label: &str,
+    button: B,
+) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    justify_content: JustifyContent::Center,
+                    padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)),
+                    ..default()
+                },
+                background_color: theme.button.into(),
+                ..default()
+            },
+            button,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font_size: 20.0,
+                    color: theme.text,
+                    ..default()
+                },
+            ));
+        });
+}
+
+pub fn despawn_settings_ui<U: Component>(mut commands: Commands, ui_query: Query<Entity, With<U>>) {
+    for entity in ui_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Hover colors of the buttons marked with `B`, presses are handled by their panel.
+pub fn highlight_settings_buttons<B: Component>(
+    mut button_query: Query<(&mut BackgroundColor, &Interaction), (Changed<Interaction>, With<B>)>,
+    theme: Res<Theme>,
+) {
+    for (mut background_color, interaction) in button_query.iter_mut() {
+        match interaction {
+            Interaction::Hovered => background_color.0 = theme.button_hover,
+            Interaction::None => background_color.0 = theme.button,
+            Interaction::Pressed => {}
+        }
+    }
+}
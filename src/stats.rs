@@ -0,0 +1,247 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    ball::Ball,
+    bricks::{describe_layout, LayoutSeed},
+    events::{BallLost, FieldCleared, GameplaySet, PaddleHit},
+    layout_debug::LayoutDebugger,
+    level::EditorLevel,
+    score::Score,
+    AppState, GameConfig, StatsExport,
+};
+
+pub const DATA_DIRECTORY: &str = "./data";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum StatsEventKind {
+    BrickDestroyed { size: f32, score: i32 },
+    PaddleHit,
+    Death,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsEvent {
+    // Seconds since the round started
+    pub time: f32,
+    #[serde(flatten)]
+    pub kind: StatsEventKind,
+}
+
+/// Statistics of the current round, shown on the game over screen.
+#[derive(Resource, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RoundStats {
+    pub won: bool,
+    pub time_played: f32,
+    // Keyed by brick size rounded to the pixel
+    pub bricks_by_size: BTreeMap<i32, i32>,
+    pub paddle_hits: i32,
+    pub max_ball_speed: f32,
+    pub events: Vec<StatsEvent>,
+}
+
+impl RoundStats {
+    pub fn record_brick(&mut self, size: f32, score: i32) {
+        *self.bricks_by_size.entry(size.round() as i32).or_insert(0) += 1;
+        self.record(StatsEventKind::BrickDestroyed { size, score });
+    }
+
+    pub fn record_paddle_hit(&mut self) {
+        self.paddle_hits += 1;
+        self.record(StatsEventKind::PaddleHit);
+    }
+
+    pub fn record_death(&mut self) {
+        self.record(StatsEventKind::Death);
+    }
+
+    fn record(&mut self, kind: StatsEventKind) {
+        self.events.push(StatsEvent {
+            time: self.time_played,
+            kind,
+        });
+    }
+
+    pub fn bricks_destroyed(&self) -> i32 {
+        self.bricks_by_size.values().sum()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct RoundRecord {
+    config: String,
+    layout: String,
+    score: i32,
+    won: bool,
+    duration: f32,
+    max_ball_speed: f32,
+    events: Vec<StatsEvent>,
+}
+
+/// Rounds of the session, written to the data directory after each round.
+#[derive(Resource, Debug, Serialize)]
+struct SessionStats {
+    started: u64,
+    rounds: Vec<RoundRecord>,
+}
+
+impl SessionStats {
+    fn to_csv(&self) -> String {
+        let mut csv =
+            String::from("round,config,layout,round_score,won,time,event,brick_size,brick_score\n");
+        for (index, round) in self.rounds.iter().enumerate() {
+            for event in round.events.iter() {
+                let (name, size, score) = match &event.kind {
+                    StatsEventKind::BrickDestroyed { size, score } => {
+                        ("brick_destroyed", size.to_string(), score.to_string())
+                    }
+                    StatsEventKind::PaddleHit => ("paddle_hit", String::new(), String::new()),
+                    StatsEventKind::Death => ("death", String::new(), String::new()),
+                };
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{},{:.3},{},{},{}",
+                    index + 1,
+                    csv_field(&round.config),
+                    csv_field(&round.layout),
+                    round.score,
+                    round.won,
+                    event.time,
+                    name,
+                    size,
+                    score,
+                );
+            }
+        }
+        csv
+    }
+
+    fn save(&self, stats_export: StatsExport) -> Result<String, String> {
+        let (extension, contents) = match stats_export {
+            StatsExport::None => return Err("Stats export is disabled".to_string()),
+            StatsExport::Json => (
+                "json",
+                serde_json::to_string_pretty(self)
+                    .map_err(|e| format!("Failed to serialize stats: {e}"))?,
+            ),
+            StatsExport::Csv => ("csv", self.to_csv()),
+        };
+        fs::create_dir_all(DATA_DIRECTORY)
+            .map_err(|e| format!("Failed to create {DATA_DIRECTORY}: {e}"))?;
+
+        let file_path = format!("{DATA_DIRECTORY}/stats_{}.{extension}", self.started);
+        fs::write(&file_path, contents).map_err(|e| format!("Failed to write {file_path}: {e}"))?;
+        Ok(file_path)
+    }
+}
+
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn session_start_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RoundStats::default())
+            .insert_resource(SessionStats {
+                started: session_start_time(),
+                rounds: Vec::new(),
+            })
+            .add_systems(OnEnter(AppState::InGame), reset_round_stats)
+            .add_systems(
+                Update,
+                update_round_stats.run_if(
+                    in_state(AppState::InGame).and_then(not(resource_exists::<LayoutDebugger>())),
+                ),
+            )
+            .add_systems(
+                Update,
+                record_round_events
+                    .in_set(GameplaySet::Consequences)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(OnEnter(AppState::GaveOver), export_round_stats);
+    }
+}
+
+fn reset_round_stats(mut round_stats: ResMut<RoundStats>) {
+    *round_stats = RoundStats::default();
+}
+
+fn update_round_stats(
+    time: Res<Time>,
+    ball_query: Query<&Velocity, With<Ball>>,
+    mut round_stats: ResMut<RoundStats>,
+) {
+    round_stats.time_played += time.delta_seconds();
+    for ball_velocity in ball_query.iter() {
+        round_stats.max_ball_speed = round_stats
+            .max_ball_speed
+            .max(ball_velocity.linvel.length());
+    }
+}
+
+/// Destroyed bricks are recorded when scored, with the combo multiplier applied.
+fn record_round_events(
+    mut paddle_hit_events: EventReader<PaddleHit>,
+    mut ball_lost_events: EventReader<BallLost>,
+    mut field_cleared_events: EventReader<FieldCleared>,
+    mut round_stats: ResMut<RoundStats>,
+) {
+    for _ in paddle_hit_events.read() {
+        round_stats.record_paddle_hit();
+    }
+    for _ in ball_lost_events.read() {
+        round_stats.record_death();
+    }
+    if field_cleared_events.read().count() > 0 {
+        round_stats.won = true;
+    }
+}
+
+fn export_round_stats(
+    mut session_stats: ResMut<SessionStats>,
+    round_stats: Res<RoundStats>,
+    score: Res<Score>,
+    layout_seed: Option<Res<LayoutSeed>>,
+    editor_level: Option<Res<EditorLevel>>,
+    game_config: Res<GameConfig>,
+) {
+    if game_config.stats_export == StatsExport::None {
+        return;
+    }
+
+    session_stats.rounds.push(RoundRecord {
+        config: game_config.file_name.clone(),
+        layout: describe_layout(layout_seed.as_deref(), editor_level.is_some(), &game_config),
+        score: score.score,
+        won: round_stats.won,
+        duration: round_stats.time_played,
+        max_ball_speed: round_stats.max_ball_speed,
+        events: round_stats.events.clone(),
+    });
+    // The whole session is rewritten so the file is complete even if the game is killed
+    match session_stats.save(game_config.stats_export) {
+        Ok(file_path) => info!("saved round stats to {file_path}"),
+        Err(e) => error!("{e}"),
+    }
+}
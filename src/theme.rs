@@ -0,0 +1,411 @@
+use bevy::prelude::*;
+use serde::{de, Deserialize, Deserializer};
+use std::{fs, path::Path};
+
+use crate::{
+    accessibility::AccessibilitySettings, bricks::get_brick_type_count, stats::DATA_DIRECTORY,
+    AppState, GameConfig,
+};
+
+pub const THEMES_DIRECTORY: &str = "./assets/themes";
+const ASSETS_DIRECTORY: &str = "./assets";
+const THEME_SELECTION_FILE: &str = "theme.txt";
+
+/// Colors and border widths of the game objects and the UI. Colors are hex strings,
+/// `"#rrggbb"` or `"#rrggbbaa"`, and omitted fields keep the built in value.
+#[derive(Resource, Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    #[serde(deserialize_with = "deserialize_color")]
+    pub background: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub ball: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub ball_border: Color,
+    pub ball_border_width: f32,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub paddle: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub paddle_border: Color,
+    pub paddle_border_width: f32,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub wall: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub death_wall: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub portal_wall: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub bouncy_wall: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub boost_wall: Color,
+    // One color per brick type, from the widest bricks down, repeated when there are more types
+    #[serde(deserialize_with = "deserialize_colors")]
+    pub bricks: Vec<Color>,
+    #[serde(deserialize_with = "deserialize_colors")]
+    pub brick_borders: Vec<Color>,
+    pub brick_border_width: f32,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub text: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub text_highlight: Color,
+    // Won games in the history
+    #[serde(deserialize_with = "deserialize_color")]
+    pub text_success: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub panel: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub final_score_panel: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub final_score_text: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub button: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub button_hover: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub button_active: Color,
+    // Outlines drawn by the level editor
+    #[serde(deserialize_with = "deserialize_color")]
+    pub editor_valid: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub editor_invalid: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub editor_hover: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub editor_bounds: Color,
+    // Images in the assets directory drawn instead of the shapes
+    pub ball_image: Option<String>,
+    pub paddle_image: Option<String>,
+    // Stretched with its corners and edges kept at `brick_border_width`
+    pub brick_image: Option<String>,
+    // Part of the brick image, at each side, kept unstretched
+    pub brick_image_slice: f32,
+    // Multiplied with the brick color, keep off for images with their own colors
+    pub brick_image_tint: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            background: Color::rgb(0.2, 0.2, 0.2),
+            ball: Color::hsl(0.0, 1.0, 0.75),
+            ball_border: Color::hsl(0.0, 1.0, 0.1),
+            ball_border_width: 2.0,
+            paddle: Color::hsl(240.0, 1.0, 0.75),
+            paddle_border: Color::hsl(240.0, 1.0, 0.1),
+            paddle_border_width: 2.0,
+            wall: Color::rgb(0.8, 0.8, 0.8),
+            death_wall: Color::rgb(0.5, 0.2, 0.2),
+            portal_wall: Color::rgb(0.4, 0.3, 0.8),
+            bouncy_wall: Color::rgb(0.3, 0.7, 0.4),
+            boost_wall: Color::rgb(0.9, 0.6, 0.2),
+            bricks: vec![
+                Color::hsl(0.0, 0.5, 0.5),   // Red
+                Color::hsl(30.0, 0.5, 0.5),  // Orange
+                Color::hsl(60.0, 0.5, 0.5),  // Yellow
+                Color::hsl(120.0, 0.5, 0.5), // Green
+                Color::hsl(240.0, 0.5, 0.5), // Blue
+                Color::hsl(270.0, 0.5, 0.5), // Indigo
+                Color::hsl(290.0, 0.5, 0.5), // Violet
+            ],
+            brick_borders: vec![
+                Color::hsl(0.0, 0.2, 0.3),   // Red
+                Color::hsl(30.0, 0.2, 0.3),  // Orange
+                Color::hsl(60.0, 0.2, 0.3),  // Yellow
+                Color::hsl(120.0, 0.2, 0.3), // Green
+                Color::hsl(240.0, 0.2, 0.3), // Blue
+                Color::hsl(270.0, 0.2, 0.3), // Indigo
+                Color::hsl(290.0, 0.2, 0.3), // Violet
+            ],
+            brick_border_width: 5.0,
+            text: Color::WHITE,
+            text_highlight: Color::rgb(1.0, 0.85, 0.3),
+            text_success: Color::rgb(0.6, 1.0, 0.6),
+            panel: Color::rgba(0.1, 0.1, 0.1, 0.9),
+            final_score_panel: Color::rgb(191.0 / 255.0, 148.0 / 255.0, 228.0 / 255.0),
+            final_score_text: Color::BLACK,
+            button: Color::rgb(0.1, 0.1, 0.1),
+            button_hover: Color::rgb(0.4, 0.4, 0.4),
+            button_active: Color::rgb(0.2, 0.3, 0.6),
+            editor_valid: Color::rgb(0.5, 1.0, 0.5),
+            editor_invalid: Color::rgb(1.0, 0.3, 0.3),
+            editor_hover: Color::WHITE,
+            editor_bounds: Color::GRAY,
+            ball_image: None,
+            paddle_image: None,
+            brick_image: None,
+            brick_image_slice: 0.25,
+            brick_image_tint: true,
+        }
+    }
+}
+
+impl Theme {
+    pub fn load(file_name: &str) -> Result<Self, String> {
+        let file_path = format!("{THEMES_DIRECTORY}/{file_name}");
+        let contents = fs::read_to_string(&file_path)
+            .map_err(|e| format!("Failed to read {file_path}: {e}"))?;
+
+        let theme: Theme = serde_yaml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {file_path}: {e}"))?;
+        if theme.bricks.is_empty() || theme.brick_borders.is_empty() {
+            return Err(format!("{file_path} needs at least one brick color"));
+        }
+        Ok(theme)
+    }
+
+    /// The theme picked on the config menu wins over the one of the config, the built in
+    /// theme is used when neither is set or loads.
+    pub fn select(theme_selection: &ThemeSelection, game_config: Option<&GameConfig>) -> Self {
+        let file_name = theme_selection
+            .0
+            .as_ref()
+            .or(game_config.and_then(|game_config| game_config.theme.as_ref()));
+        match file_name.map(|file_name| Theme::load(file_name)) {
+            Some(Ok(theme)) => theme,
+            Some(Err(e)) => {
+                error!("{e}");
+                Theme::default()
+            }
+            None => Theme::default(),
+        }
+    }
+
+    pub fn brick_color(&self, brick_type: usize) -> Color {
+        self.bricks[brick_type % self.bricks.len()]
+    }
+
+    pub fn brick_border_color(&self, brick_type: usize) -> Color {
+        self.brick_borders[brick_type % self.brick_borders.len()]
+    }
+}
+
+/// Images of the theme, `None` when not set or missing, the shapes are drawn instead.
+#[derive(Resource, Debug, Default)]
+pub struct Skins {
+    pub ball: Option<Handle<Image>>,
+    pub paddle: Option<Handle<Image>>,
+    pub brick: Option<Handle<Image>>,
+}
+
+impl Skins {
+    fn load(theme: &Theme, asset_server: &AssetServer) -> Self {
+        let load = |file_name: &Option<String>| {
+            let file_name = file_name.as_ref()?;
+            if !Path::new(ASSETS_DIRECTORY).join(file_name).is_file() {
+                error!("Skin image {ASSETS_DIRECTORY}/{file_name} not found");
+                return None;
+            }
+            Some(asset_server.load(file_name.clone()))
+        };
+        Skins {
+            ball: load(&theme.ball_image),
+            paddle: load(&theme.paddle_image),
+            brick: load(&theme.brick_image),
+        }
+    }
+}
+
+fn deserialize_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    Color::hex(&hex).map_err(|e| de::Error::custom(format!("invalid color {hex}: {e}")))
+}
+
+fn deserialize_colors<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Color>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|hex| {
+            Color::hex(hex).map_err(|e| de::Error::custom(format!("invalid color {hex}: {e}")))
+        })
+        .collect()
+}
+
+/// Theme file picked on the config menu for every config, `None` leaves it to the config.
+#[derive(Resource, Debug, Clone, PartialEq, Default)]
+pub struct ThemeSelection(pub Option<String>);
+
+impl ThemeSelection {
+    fn load() -> Self {
+        let file_name = fs::read_to_string(format!("{DATA_DIRECTORY}/{THEME_SELECTION_FILE}"))
+            .map(|contents| contents.trim().to_string())
+            .unwrap_or_default();
+        ThemeSelection((!file_name.is_empty()).then_some(file_name))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let file_path = format!("{DATA_DIRECTORY}/{THEME_SELECTION_FILE}");
+        fs::create_dir_all(DATA_DIRECTORY)
+            .map_err(|e| format!("Failed to create {DATA_DIRECTORY}: {e}"))?;
+
+        fs::write(&file_path, self.0.as_deref().unwrap_or_default())
+            .map_err(|e| format!("Failed to write {file_path}: {e}"))
+    }
+}
+
+fn list_themes() -> Vec<String> {
+    let mut themes: Vec<String> = fs::read_dir(THEMES_DIRECTORY)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| {
+                    let file_name = entry.ok()?.file_name().to_string_lossy().into_owned();
+                    file_name.ends_with(".yaml").then_some(file_name)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    themes.sort();
+    themes
+}
+
+#[derive(Component, Debug)]
+struct ThemeButton;
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        let theme_selection = ThemeSelection::load();
+        let theme = Theme::select(&theme_selection, None);
+        app.insert_resource(ClearColor(theme.background))
+            .insert_resource(theme)
+            .insert_resource(theme_selection)
+            .insert_resource(Skins::default())
+            // Game objects are spawned when entering the next state, after this has run
+            .add_systems(OnExit(AppState::SelectConfig), apply_config_theme)
+            .add_systems(OnEnter(AppState::SelectConfig), spawn_theme_button)
+            .add_systems(OnExit(AppState::SelectConfig), despawn_theme_button)
+            .add_systems(
+                Update,
+                (
+                    handle_theme_click,
+                    apply_selected_theme.run_if(
+                        resource_changed::<ThemeSelection>()
+                            .or_else(resource_changed::<AccessibilitySettings>()),
+                    ),
+                    (despawn_theme_button, spawn_theme_button)
+                        .run_if(resource_changed::<ThemeSelection>()),
+                )
+                    .chain()
+                    .run_if(in_state(AppState::SelectConfig)),
+            )
+            .add_systems(Update, save_theme_selection);
+    }
+}
+
+fn apply_config_theme(
+    mut commands: Commands,
+    theme_selection: Res<ThemeSelection>,
+    accessibility_settings: Res<AccessibilitySettings>,
+    asset_server: Res<AssetServer>,
+    game_config: Res<GameConfig>,
+) {
+    let mut theme = Theme::select(&theme_selection, Some(&game_config));
+    accessibility_settings
+        .brick_palette
+        .apply(&mut theme, Some(get_brick_type_count(&game_config)));
+    // Images of the config win over those of the theme
+    for (config_image, theme_image) in [
+        (&game_config.ball_image, &mut theme.ball_image),
+        (&game_config.paddle_image, &mut theme.paddle_image),
+        (&game_config.brick_image, &mut theme.brick_image),
+    ] {
+        if config_image.is_some() {
+            theme_image.clone_from(config_image);
+        }
+    }
+    commands.insert_resource(Skins::load(&theme, &asset_server));
+    commands.insert_resource(ClearColor(theme.background));
+    commands.insert_resource(theme);
+}
+
+fn apply_selected_theme(
+    mut commands: Commands,
+    mut theme: ResMut<Theme>,
+    theme_selection: Res<ThemeSelection>,
+    accessibility_settings: Res<AccessibilitySettings>,
+) {
+    *theme = Theme::select(&theme_selection, None);
+    accessibility_settings.brick_palette.apply(&mut theme, None);
+    commands.insert_resource(ClearColor(theme.background));
+}
+
+fn save_theme_selection(theme_selection: Res<ThemeSelection>) {
+    if !theme_selection.is_changed() || theme_selection.is_added() {
+        return;
+    }
+    if let Err(e) = theme_selection.save() {
+        error!("{e}");
+    }
+}
+
+fn spawn_theme_button(
+    mut commands: Commands,
+    theme: Res<Theme>,
+    theme_selection: Res<ThemeSelection>,
+) {
+    commands
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    padding: UiRect::axes(Val::Px(8.0), Val::Px(2.0)),
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..default()
+                },
+                background_color: theme.button.into(),
+                ..default()
+            },
+            ThemeButton,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                format!(
+                    "Theme: {}",
+                    theme_selection.0.as_deref().unwrap_or("per config")
+                ),
+                TextStyle {
+                    font_size: 20.0,
+                    color: theme.text,
+                    ..default()
+                },
+            ));
+        });
+}
+
+fn despawn_theme_button(mut commands: Commands, button_query: Query<Entity, With<ThemeButton>>) {
+    for entity in button_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Cycles through the theme files, then back to the theme of each config.
+fn handle_theme_click(
+    mut button_query: Query<
+        (&mut BackgroundColor, &Interaction),
+        (Changed<Interaction>, With<ThemeButton>),
+    >,
+    mut theme_selection: ResMut<ThemeSelection>,
+    theme: Res<Theme>,
+) {
+    for (mut background_color, interaction) in button_query.iter_mut() {
+        match interaction {
+            Interaction::Pressed => {
+                let themes = list_themes();
+                let next = match &theme_selection.0 {
+                    Some(current) => themes
+                        .iter()
+                        .position(|theme| theme == current)
+                        .and_then(|index| themes.get(index + 1)),
+                    None => themes.first(),
+                };
+                theme_selection.0 = next.cloned();
+            }
+            Interaction::Hovered => {
+                background_color.0 = theme.button_hover;
+            }
+            Interaction::None => {
+                background_color.0 = theme.button;
+            }
+        }
+    }
+}
@@ -1,7 +1,13 @@
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
+use std::f32::consts::{PI, SQRT_2, TAU};
 
-use crate::{AppState, GameConfig};
+use crate::{
+    theme::Theme, AppState, ArenaShape, GameConfig, PaddleSide, WallBehavior,
+    DEGREE_TO_RADIAN_FACTOR,
+};
+
+const ARENA_CIRCLE_SIDES: i32 = 64;
 
 // const WALL_THICKNESS: f32 = 10.0;
 // // x coordinates
@@ -10,8 +16,6 @@ use crate::{AppState, GameConfig};
 // // y coordinates
 // const BOTTOM_WALL: f32 = -400.0;
 // const TOP_WALL: f32 = 400.0;
-const WALL_COLOR: Color = Color::rgb(0.8, 0.8, 0.8);
-const DEATH_WALL_COLOR: Color = Color::rgb(0.5, 0.2, 0.2);
 
 #[derive(Component, Debug, Clone)]
 pub enum WallLocation {
@@ -24,6 +28,26 @@ pub enum WallLocation {
 #[derive(Component, Debug)]
 pub struct Death;
 
+/// Wall the ball passes through to come back in from the opposite side, `distance` is from
+/// the middle of the arena to the inner side of the wall along its outward `normal`.
+#[derive(Component, Debug)]
+pub struct Portal {
+    pub normal: Vec2,
+    pub distance: f32,
+}
+
+/// Wall bouncing the ball back faster than it came.
+#[derive(Component, Debug)]
+pub struct Bouncy;
+
+/// Wall speeding the ball up.
+#[derive(Component, Debug)]
+pub struct Boost;
+
+/// Corner bumper or obstacle inside the arena.
+#[derive(Component, Debug)]
+pub struct Obstacle;
+
 impl WallLocation {
     fn position(&self, game_config: &Res<GameConfig>) -> Vec2 {
         match self {
@@ -45,23 +69,161 @@ impl WallLocation {
             ),
         }
     }
+
+    fn normal(&self) -> Vec2 {
+        match self {
+            WallLocation::Left => Vec2::NEG_X,
+            WallLocation::Right => Vec2::X,
+            WallLocation::Bottom => Vec2::NEG_Y,
+            WallLocation::Top => Vec2::Y,
+        }
+    }
+
+    fn opposite(&self) -> WallLocation {
+        match self {
+            WallLocation::Left => WallLocation::Right,
+            WallLocation::Right => WallLocation::Left,
+            WallLocation::Bottom => WallLocation::Top,
+            WallLocation::Top => WallLocation::Bottom,
+        }
+    }
+
+    fn behavior(&self, game_config: &GameConfig) -> WallBehavior {
+        match self {
+            WallLocation::Left => game_config.wall_left,
+            WallLocation::Right => game_config.wall_right,
+            WallLocation::Bottom => game_config.get_wall_bottom(),
+            WallLocation::Top => game_config.get_wall_top(),
+        }
+    }
 }
 
 pub struct WallPlugin;
 
 impl Plugin for WallPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(AppState::InGame), spawn_walls);
+        app.add_systems(OnEnter(AppState::InGame), spawn_walls)
+            .add_systems(OnEnter(AppState::Editor), spawn_walls)
+            .add_systems(OnEnter(AppState::SelectConfig), despawn_walls);
     }
 }
 
-fn spawn_walls(mut commands: Commands, game_config: Res<GameConfig>) {
+fn despawn_walls(
+    mut commands: Commands,
+    walls_query: Query<Entity, Or<(With<WallLocation>, With<Obstacle>)>>,
+) {
+    for wall_entity in walls_query.iter() {
+        commands.entity(wall_entity).despawn_recursive();
+    }
+}
+
+fn spawn_walls(
+    mut commands: Commands,
+    walls_query: Query<Entity, Or<(With<WallLocation>, With<Obstacle>)>>,
+    theme: Res<Theme>,
+    game_config: Res<GameConfig>,
+) {
+    for wall_entity in walls_query.iter() {
+        commands.entity(wall_entity).despawn_recursive();
+    }
+
+    for obstacle in get_arena_obstacles(&game_config) {
+        commands.spawn((
+            Obstacle,
+            SpriteBundle {
+                // Under the walls, which hide the outer half of the corner bumpers
+                transform: obstacle.with_translation(obstacle.translation.truncate().extend(0.5)),
+                sprite: Sprite {
+                    color: theme.wall,
+                    ..default()
+                },
+                ..default()
+            },
+            RigidBody::Fixed,
+            Collider::cuboid(0.5, 0.5),
+            Friction::coefficient(0.0),
+            Restitution::coefficient(1.0),
+        ));
+    }
+
+    if let Some(boundary) = get_arena_boundary(&game_config) {
+        let sides: Vec<(Vec2, Vec2, Vec2, WallLocation)> = boundary
+            .iter()
+            .zip(boundary.iter().cycle().skip(1))
+            .map(|(a, b)| {
+                // The corners are counterclockwise, the outside is on the right of each side
+                let normal = -(*b - *a).normalize().perp();
+                let wall_location = if normal.x.abs() > normal.y.abs() {
+                    if normal.x < 0.0 {
+                        WallLocation::Left
+                    } else {
+                        WallLocation::Right
+                    }
+                } else if normal.y < 0.0 {
+                    WallLocation::Bottom
+                } else {
+                    WallLocation::Top
+                };
+                (*a, *b, normal, wall_location)
+            })
+            .collect();
+        let mut unpaired_wrap = false;
+        for (a, b, normal, wall_location) in sides.iter() {
+            let direction = (*b - *a).normalize();
+            let transform = Transform {
+                translation: ((*a + *b) / 2.0 + *normal * game_config.wall_thickness / 2.0)
+                    .extend(0.0),
+                rotation: Quat::from_rotation_z(direction.y.atan2(direction.x)),
+                // Overlapping the next sides so the corners are closed
+                scale: Vec3::new(
+                    a.distance(*b) + game_config.wall_thickness,
+                    game_config.wall_thickness,
+                    1.0,
+                ),
+            };
+            let mut behavior = wall_location.behavior(&game_config);
+            if behavior == WallBehavior::Wrap {
+                // The ball comes back in from the side facing this one across the arena
+                let shift = 2.0 * a.dot(*normal) * *normal;
+                let paired = sides.iter().any(|(c, d, _, location)| {
+                    location.behavior(&game_config) == WallBehavior::Wrap
+                        && c.distance(*b - shift) < 0.01
+                        && d.distance(*a - shift) < 0.01
+                });
+                if !paired {
+                    unpaired_wrap = true;
+                    behavior = WallBehavior::Solid;
+                }
+            }
+            spawn_wall(
+                &mut commands,
+                wall_location.clone(),
+                transform,
+                *normal,
+                behavior,
+                &theme,
+                &game_config,
+            );
+        }
+        if unpaired_wrap {
+            warn!("Sides with no wrapping side facing them across the arena are solid");
+        }
+        return;
+    }
+
     for wall_location in [
         WallLocation::Left,
         WallLocation::Right,
         WallLocation::Bottom,
         WallLocation::Top,
     ] {
+        let mut behavior = wall_location.behavior(&game_config);
+        if behavior == WallBehavior::Wrap
+            && wall_location.opposite().behavior(&game_config) != WallBehavior::Wrap
+        {
+            warn!("The {wall_location:?} wall has no opposite wall to wrap to, it is solid");
+            behavior = WallBehavior::Solid;
+        }
         let position = wall_location.position(&game_config);
         let scale = match wall_location {
             WallLocation::Left | WallLocation::Right => Vec2::new(
@@ -74,33 +236,427 @@ fn spawn_walls(mut commands: Commands, game_config: Res<GameConfig>) {
             ),
         };
 
-        let mut entity_commands = commands.spawn((
-            wall_location.clone(),
-            SpriteBundle {
-                transform: Transform {
-                    translation: match wall_location {
-                        WallLocation::Bottom => position.extend(0.0),
-                        _ => position.extend(1.0),
-                    },
-                    scale: scale.extend(1.0),
-                    ..default()
-                },
-                sprite: Sprite {
-                    color: match wall_location {
-                        WallLocation::Bottom => DEATH_WALL_COLOR,
-                        _ => WALL_COLOR,
-                    },
+        let z = match wall_location {
+            WallLocation::Bottom => 0.0,
+            _ => 1.0,
+        };
+        let faces_paddle = match game_config.paddle_side {
+            PaddleSide::Bottom => matches!(wall_location, WallLocation::Top),
+            PaddleSide::Top => matches!(wall_location, WallLocation::Bottom),
+        };
+        if faces_paddle {
+            // Split around the openings, which are walls the ball is lost on
+            let mut pieces = Vec::new();
+            let mut left = position.x - scale.x / 2.0;
+            let mut openings = game_config.arena_top_openings.clone();
+            openings.sort_by(|a, b| a.x.total_cmp(&b.x));
+            for opening in openings.iter() {
+                let start = (opening.x - opening.width / 2.0).max(left);
+                let end = (opening.x + opening.width / 2.0).min(position.x + scale.x / 2.0);
+                if end <= start {
+                    continue;
+                }
+                pieces.push((left, start, behavior));
+                pieces.push((start, end, WallBehavior::Death));
+                left = end;
+            }
+            pieces.push((left, position.x + scale.x / 2.0, behavior));
+
+            for (start, end, behavior) in pieces {
+                if end <= start {
+                    continue;
+                }
+                let transform = Transform {
+                    translation: Vec3::new((start + end) / 2.0, position.y, z),
+                    scale: Vec3::new(end - start, scale.y, 1.0),
                     ..default()
+                };
+                spawn_wall(
+                    &mut commands,
+                    wall_location.clone(),
+                    transform,
+                    wall_location.normal(),
+                    behavior,
+                    &theme,
+                    &game_config,
+                );
+            }
+            continue;
+        }
+
+        let transform = Transform {
+            translation: position.extend(z),
+            scale: scale.extend(1.0),
+            ..default()
+        };
+        let normal = wall_location.normal();
+        spawn_wall(
+            &mut commands,
+            wall_location,
+            transform,
+            normal,
+            behavior,
+            &theme,
+            &game_config,
+        );
+    }
+}
+
+fn spawn_wall(
+    commands: &mut Commands,
+    wall_location: WallLocation,
+    transform: Transform,
+    normal: Vec2,
+    behavior: WallBehavior,
+    theme: &Theme,
+    game_config: &GameConfig,
+) {
+    let mut entity_commands = commands.spawn((
+        wall_location,
+        SpriteBundle {
+            transform,
+            sprite: Sprite {
+                color: match behavior {
+                    WallBehavior::Solid => theme.wall,
+                    WallBehavior::Death => theme.death_wall,
+                    WallBehavior::Wrap => theme.portal_wall,
+                    WallBehavior::Bouncy => theme.bouncy_wall,
+                    WallBehavior::Boost => theme.boost_wall,
                 },
                 ..default()
             },
-            RigidBody::Fixed,
-            Collider::cuboid(0.5, 0.5),
-            Friction::coefficient(0.0),
-            Restitution::coefficient(1.0),
-        ));
-        if let WallLocation::Bottom = wall_location {
+            ..default()
+        },
+        RigidBody::Fixed,
+        Collider::cuboid(0.5, 0.5),
+        Friction::coefficient(0.0),
+        Restitution::coefficient(1.0),
+    ));
+    match behavior {
+        WallBehavior::Solid => {}
+        WallBehavior::Death => {
             entity_commands.insert(Death);
         }
+        WallBehavior::Wrap => {
+            // The wall is centered half its thickness out of the arena
+            let inner_side =
+                transform.translation.truncate() - normal * game_config.wall_thickness / 2.0;
+            entity_commands.insert((
+                Sensor,
+                Portal {
+                    normal,
+                    distance: inner_side.dot(normal),
+                },
+            ));
+        }
+        WallBehavior::Bouncy => {
+            // The largest of the ball and wall restitutions is used
+            entity_commands.insert((
+                Bouncy,
+                Restitution {
+                    coefficient: game_config.wall_bounce_restitution,
+                    combine_rule: CoefficientCombineRule::Max,
+                },
+            ));
+        }
+        WallBehavior::Boost => {
+            entity_commands.insert(Boost);
+        }
+    }
+}
+
+/// Inner corners of a circular or polygonal arena, counterclockwise and stretched to fill the
+/// area. Below its widest point the sides run straight down to a flat floor as wide as the area,
+/// so the paddle can travel as far as in the box, upside down with the paddle at the top. `None`
+/// for the box.
+pub fn get_arena_boundary(game_config: &GameConfig) -> Option<Vec<Vec2>> {
+    let sides = match game_config.arena_shape {
+        ArenaShape::Box => return None,
+        ArenaShape::Circle => ARENA_CIRCLE_SIDES,
+        ArenaShape::Polygon => game_config.arena_sides.max(3),
+    };
+    // Turned so the bottom side is flat under the paddle
+    let start = -PI / 2.0 + PI / sides as f32;
+    let corners: Vec<Vec2> = (0..sides)
+        .map(|i| Vec2::from_angle(start + i as f32 * TAU / sides as f32))
+        .collect();
+    let extent = corners
+        .iter()
+        .fold(Vec2::ZERO, |extent, corner| extent.max(corner.abs()));
+    let half_size = Vec2::new(game_config.area_width, game_config.area_height) / 2.0;
+    let corners: Vec<Vec2> = corners
+        .iter()
+        .map(|corner| *corner / extent * half_size)
+        .collect();
+
+    // The arena is symmetric, the highest of the rightmost corners is mirrored by the last
+    let widest = corners
+        .iter()
+        .map(|corner| corner.x)
+        .fold(f32::MIN, f32::max);
+    let first = (0..corners.len())
+        .filter(|i| corners[*i].x >= widest - 0.001 * half_size.x)
+        .max_by(|a, b| corners[*a].y.total_cmp(&corners[*b].y))
+        .unwrap_or(0);
+    let last = corners.len() - 1 - first;
+    let mut boundary = vec![Vec2::new(half_size.x, -half_size.y)];
+    boundary.extend(corners[first..=last].iter().copied());
+    boundary.push(Vec2::new(-half_size.x, -half_size.y));
+    boundary.dedup_by(|a, b| a.distance(*b) < 0.001 * half_size.x);
+    if game_config.paddle_side == PaddleSide::Top {
+        // Mirrored corners run clockwise, reversed to stay counterclockwise
+        boundary = boundary
+            .iter()
+            .rev()
+            .map(|corner| Vec2::new(corner.x, -corner.y))
+            .collect();
+    }
+    Some(boundary)
+}
+
+/// Corner bumpers and obstacles of the config, as transforms of unit squares.
+pub fn get_arena_obstacles(game_config: &GameConfig) -> Vec<Transform> {
+    let mut obstacles = Vec::new();
+    if game_config.arena_shape == ArenaShape::Box && game_config.arena_corner_bumper > 0.0 {
+        // Squares turned by 45 degrees on the corners across from the paddle, half of their
+        // diagonal long
+        let side = game_config.arena_corner_bumper * SQRT_2;
+        for x in [-game_config.area_width / 2.0, game_config.area_width / 2.0] {
+            obstacles.push(Transform {
+                translation: Vec3::new(
+                    x,
+                    game_config.area_height / 2.0 * game_config.get_paddle_side_sign(),
+                    0.0,
+                ),
+                rotation: Quat::from_rotation_z(PI / 4.0),
+                scale: Vec3::new(side, side, 1.0),
+            });
+        }
+    }
+    obstacles.extend(
+        game_config
+            .arena_obstacles
+            .iter()
+            .map(|obstacle| Transform {
+                translation: Vec3::new(obstacle.x, obstacle.y, 0.0),
+                rotation: Quat::from_rotation_z(obstacle.angle * DEGREE_TO_RADIAN_FACTOR),
+                scale: Vec3::new(obstacle.width, obstacle.height, 1.0),
+            }),
+    );
+    obstacles
+}
+
+/// Distance from the middle of the arena to its side at height `y`.
+pub fn get_arena_half_width(y: f32, game_config: &GameConfig) -> f32 {
+    let Some(boundary) = get_arena_boundary(game_config) else {
+        return game_config.area_width / 2.0;
+    };
+    boundary
+        .iter()
+        .zip(boundary.iter().cycle().skip(1))
+        .filter(|(a, b)| a.y != b.y && (a.y - y) * (b.y - y) <= 0.0)
+        .map(|(a, b)| (a.x + (y - a.y) / (b.y - a.y) * (b.x - a.x)).abs())
+        .reduce(f32::min)
+        .unwrap_or(0.0)
+}
+
+/// Whether a brick is inside the boundary of the arena and off its obstacles, as returned by
+/// `get_arena_boundary` and `get_arena_obstacles`.
+pub fn is_inside_arena(
+    square: &Transform,
+    boundary: Option<&[Vec2]>,
+    obstacles: &[Transform],
+) -> bool {
+    let corners = transform_corners(square);
+    if let Some(boundary) = boundary {
+        let inside = corners.iter().all(|corner| {
+            boundary
+                .iter()
+                .zip(boundary.iter().cycle().skip(1))
+                .all(|(a, b)| (*b - *a).perp_dot(*corner - *a) >= 0.0)
+        });
+        if !inside {
+            return false;
+        }
+    }
+    obstacles
+        .iter()
+        .all(|obstacle| !polygons_overlap(&corners, &transform_corners(obstacle)))
+}
+
+fn transform_corners(transform: &Transform) -> Vec<Vec2> {
+    [
+        Vec2::new(-0.5, -0.5),
+        Vec2::new(0.5, -0.5),
+        Vec2::new(0.5, 0.5),
+        Vec2::new(-0.5, 0.5),
+    ]
+    .iter()
+    .map(|corner| transform.transform_point(corner.extend(0.0)).truncate())
+    .collect()
+}
+
+/// Separating axis test of two convex polygons, touching sides don't overlap.
+fn polygons_overlap(a: &[Vec2], b: &[Vec2]) -> bool {
+    let project = |polygon: &[Vec2], axis: Vec2| {
+        polygon
+            .iter()
+            .map(|point| point.dot(axis))
+            .fold((f32::MAX, f32::MIN), |(min, max), d| {
+                (min.min(d), max.max(d))
+            })
+    };
+    a.iter()
+        .zip(a.iter().cycle().skip(1))
+        .chain(b.iter().zip(b.iter().cycle().skip(1)))
+        .all(|(start, end)| {
+            let axis = (*end - *start).perp();
+            let (a_min, a_max) = project(a, axis);
+            let (b_min, b_max) = project(b, axis);
+            a_max > b_min && b_max > a_min
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ArenaObstacle;
+
+    fn config_with_shape(arena_shape: ArenaShape, arena_sides: i32) -> GameConfig {
+        let mut game_config = GameConfig::parse("assets/default.yaml").unwrap();
+        game_config.arena_shape = arena_shape;
+        game_config.arena_sides = arena_sides;
+        game_config
+    }
+
+    fn is_inside(square: &Transform, game_config: &GameConfig) -> bool {
+        is_inside_arena(
+            square,
+            get_arena_boundary(game_config).as_deref(),
+            &get_arena_obstacles(game_config),
+        )
+    }
+
+    fn square(x: f32, y: f32, size: f32) -> Transform {
+        Transform::from_xyz(x, y, 0.0).with_scale(Vec3::new(size, size, 1.0))
+    }
+
+    #[test]
+    fn box_half_width_is_constant() {
+        let game_config = config_with_shape(ArenaShape::Box, 6);
+        for y in [
+            -game_config.area_height / 2.0,
+            0.0,
+            game_config.area_height / 2.0,
+        ] {
+            assert_eq!(
+                get_arena_half_width(y, &game_config),
+                game_config.area_width / 2.0
+            );
+        }
+    }
+
+    #[test]
+    fn circle_floor_is_as_wide_as_the_area() {
+        let game_config = config_with_shape(ArenaShape::Circle, 6);
+        let half_width = game_config.area_width / 2.0;
+        let floor = -game_config.area_height / 2.0;
+        for y in [floor, floor + game_config.paddle_height, 0.0] {
+            assert!((get_arena_half_width(y, &game_config) - half_width).abs() < 1e-2);
+        }
+        let near_top = get_arena_half_width(game_config.area_height * 0.45, &game_config);
+        assert!(near_top < half_width / 2.0);
+    }
+
+    #[test]
+    fn floor_is_under_the_paddle_at_the_top() {
+        let mut game_config = config_with_shape(ArenaShape::Circle, 6);
+        game_config.paddle_side = PaddleSide::Top;
+        let half_width = game_config.area_width / 2.0;
+        let ceiling = game_config.area_height / 2.0;
+        for y in [ceiling, ceiling - game_config.paddle_height] {
+            assert!((get_arena_half_width(y, &game_config) - half_width).abs() < 1e-2);
+        }
+        let near_bottom = get_arena_half_width(-game_config.area_height * 0.45, &game_config);
+        assert!(near_bottom < half_width / 2.0);
+    }
+
+    #[test]
+    fn polygon_narrows_above_its_widest_corners() {
+        let game_config = config_with_shape(ArenaShape::Polygon, 6);
+        let half_width = game_config.area_width / 2.0;
+        assert!((get_arena_half_width(0.0, &game_config) - half_width).abs() < 1e-2);
+        // Halfway up the slanted sides the hexagon is three quarters as wide as in the middle
+        let slanted = get_arena_half_width(game_config.area_height / 4.0, &game_config);
+        assert!((slanted - half_width * 0.75).abs() < 1e-2);
+    }
+
+    #[test]
+    fn touching_polygons_do_not_overlap() {
+        let a = transform_corners(&square(0.0, 0.0, 10.0));
+        assert!(polygons_overlap(
+            &a,
+            &transform_corners(&square(5.0, 5.0, 10.0))
+        ));
+        assert!(!polygons_overlap(
+            &a,
+            &transform_corners(&square(10.0, 0.0, 10.0))
+        ));
+        assert!(!polygons_overlap(
+            &a,
+            &transform_corners(&square(30.0, 0.0, 10.0))
+        ));
+    }
+
+    #[test]
+    fn turned_polygons_overlap_only_past_their_corners() {
+        let a = transform_corners(&square(0.0, 0.0, 10.0));
+        // Diamond whose left corner reaches 5 * sqrt(2) from its middle
+        let diamond = |x: f32| {
+            transform_corners(&square(x, 0.0, 10.0).with_rotation(Quat::from_rotation_z(PI / 4.0)))
+        };
+        assert!(polygons_overlap(&a, &diamond(11.0)));
+        assert!(!polygons_overlap(&a, &diamond(13.0)));
+    }
+
+    #[test]
+    fn bricks_stay_inside_the_boundary() {
+        let game_config = config_with_shape(ArenaShape::Circle, 6);
+        assert!(is_inside(&square(0.0, 0.0, 50.0), &game_config));
+        let top_corner = Vec2::new(game_config.area_width, game_config.area_height) * 0.45;
+        assert!(!is_inside(
+            &square(top_corner.x, top_corner.y, 10.0),
+            &game_config
+        ));
+        let floor = -game_config.area_height / 2.0;
+        let bottom_corner = Vec2::new(game_config.area_width / 2.0 - 10.0, floor + 10.0);
+        assert!(is_inside(
+            &square(bottom_corner.x, bottom_corner.y, 10.0),
+            &game_config
+        ));
+    }
+
+    #[test]
+    fn bricks_stay_off_obstacles_and_bumpers() {
+        let mut game_config = config_with_shape(ArenaShape::Box, 6);
+        game_config.arena_obstacles.push(ArenaObstacle {
+            x: 0.0,
+            y: 0.0,
+            width: 100.0,
+            height: 20.0,
+            angle: 0.0,
+        });
+        game_config.arena_corner_bumper = 50.0;
+        assert!(!is_inside(&square(40.0, 10.0, 10.0), &game_config));
+        assert!(is_inside(&square(0.0, 20.0, 10.0), &game_config));
+        let corner = Vec2::new(game_config.area_width, game_config.area_height) / 2.0;
+        assert!(!is_inside(
+            &square(corner.x - 10.0, corner.y - 10.0, 10.0),
+            &game_config
+        ));
+        assert!(is_inside(
+            &square(corner.x - 10.0, corner.y - 60.0, 10.0),
+            &game_config
+        ));
     }
 }